@@ -53,6 +53,11 @@ fn main() {
         SubCmd::TogglePauseWallpaper { monitors } => IpcMessage::TogglePauseWallpaper {
             monitors: monitors.into_iter().map(unquote).collect(),
         },
+        SubCmd::CaptureWallpaper { monitor, path } => IpcMessage::CaptureWallpaper {
+            monitor: unquote(monitor),
+            // The daemon runs in another directory
+            path: std::env::current_dir().unwrap().join(path),
+        },
     };
     conn.write_all(&serde_json::to_vec(&msg).unwrap()).unwrap();
     let mut buf = String::new();
@@ -97,6 +102,9 @@ fn main() {
                     eprintln!("Wallpaper could not be drawn for monitor {monitor}: {err}")
                 }
             }
+            IpcError::CaptureError { monitor, error } => {
+                eprintln!("Wallpaper could not be captured for monitor {monitor}: {error}")
+            }
         },
     }
 }
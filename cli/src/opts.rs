@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
 #[derive(Parser)]
@@ -28,4 +30,7 @@ pub enum SubCmd {
     ResumeWallpaper { monitors: Vec<String> },
     #[clap(visible_alias = "toggle-pause")]
     TogglePauseWallpaper { monitors: Vec<String> },
+    /// Save what is drawn on the monitor as an image
+    #[clap(visible_alias = "capture")]
+    CaptureWallpaper { monitor: String, path: PathBuf },
 }
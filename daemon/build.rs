@@ -52,10 +52,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Registry::new(
         Api::Gles2,
-        (2, 0),
+        // The shaders need openGL ES 3.2 anyway, it also brings the 10-bit and half float
+        // formats
+        (3, 2),
         Profile::Core,
         Fallbacks::All,
-        ["GL_EXT_texture_border_clamp"],
+        [
+            "GL_EXT_texture_border_clamp",
+            "GL_EXT_sRGB",
+            "GL_EXT_texture_filter_anisotropic",
+        ],
     )
     .write_bindings(StructGenerator, &mut file)
     .unwrap();
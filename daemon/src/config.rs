@@ -23,7 +23,10 @@ use smithay_client_toolkit::reexports::calloop::ping::Ping;
 use crate::{
     image_picker::ImagePicker,
     render::Transition,
-    wallpaper_info::{BackgroundMode, Sorting, WallpaperInfo},
+    wallpaper_info::{
+        BackgroundMode, ColorSource, Easing, Gradient, Offset, Sorting, TextureFilter,
+        WallpaperInfo, WrapMode,
+    },
 };
 
 #[derive(Default, Deserialize, PartialEq, Debug, Clone)]
@@ -39,6 +42,11 @@ pub struct SerializedWallpaperInfo {
     pub mode: Option<BackgroundMode>,
     #[serde(rename = "queue-size")]
     pub queue_size: Option<usize>,
+    /// Number of textures of the previous wallpapers kept in the GPU memory
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "texture-cache-size")]
+    pub texture_cache_size: Option<usize>,
     #[serde(rename = "transition-time")]
     pub transition_time: Option<u32>,
 
@@ -49,24 +57,174 @@ pub struct SerializedWallpaperInfo {
     /// See [crate::wallpaper_info::WallpaperInfo]
     #[serde(rename = "initial-transition")]
     pub initial_transition: Option<bool>,
+    /// Fade every wallpaper in from black, instead of transitioning from the previous one
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "fade-from-black")]
+    pub fade_from_black: Option<bool>,
     pub transition: Option<Transition>,
+    /// Easing curve applied to the progress of the transition
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub easing: Option<Easing>,
+    /// Maximum number of frames per second drawn during the transition, from 1 to 1000
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "max-fps")]
+    pub max_fps: Option<u32>,
+    /// Number of frames to wait for when swapping the buffers
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "swap-interval")]
+    pub swap_interval: Option<i32>,
 
-    /// Determine the offset for the wallpaper to be drawn into the screen
+    /// Determine the offset for the wallpaper to be drawn into the screen, either a single
+    /// value for both axes or `[x, y]`
     /// Must be from 0.0 to 1.0, by default is 0.0 in tile mode and 0.5 in all the others
     ///
     /// See [crate::wallpaper_info::WallpaperInfo]
-    pub offset: Option<f32>,
+    pub offset: Option<Offset>,
+
+    /// Mirror the wallpaper horizontally and vertically
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "flip-horizontal")]
+    pub flip_horizontal: Option<bool>,
+    #[serde(rename = "flip-vertical")]
+    pub flip_vertical: Option<bool>,
+
+    /// Size of the tiles relative to the size of the image, in tile mode
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "tile-scale")]
+    pub tile_scale: Option<f32>,
+
+    /// Slow zoom and pan of the wallpaper in center mode
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "ken-burns-zoom")]
+    pub ken_burns_zoom: Option<f32>,
+    #[serde(rename = "ken-burns-time")]
+    pub ken_burns_time: Option<u32>,
+
+    /// Space left around the wallpaper and radius of its corners, in pixels
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub padding: Option<u32>,
+    #[serde(rename = "corner-radius")]
+    pub corner_radius: Option<u32>,
+
+    /// Color used to fill the empty space around the wallpaper in fit mode, as RGBA values
+    /// from 0.0 to 1.0
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "fill-color")]
+    pub fill_color: Option<[f32; 4]>,
+
+    /// Filter used to sample the wallpaper when it gets scaled
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "texture-filter")]
+    pub texture_filter: Option<TextureFilter>,
+    /// How the wallpaper is sampled outside of its image
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "wrap-mode")]
+    pub wrap_mode: Option<WrapMode>,
+    /// Level of anisotropic filtering
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub anisotropy: Option<f32>,
+    /// Generate the mipmap of the wallpapers
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub mipmaps: Option<bool>,
+
+    /// Color adjustments applied to the wallpaper
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub brightness: Option<f32>,
+    pub contrast: Option<f32>,
+    pub gamma: Option<f32>,
+    /// Multiplier of the red, green and blue channels of the wallpaper
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub tint: Option<[f32; 3]>,
+
+    /// Darken the edges of the wallpaper
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "vignette-strength")]
+    pub vignette_strength: Option<f32>,
+    #[serde(rename = "vignette-radius")]
+    pub vignette_radius: Option<f32>,
+
+    /// Opacity of the wallpaper
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub opacity: Option<f32>,
+
+    /// Standard deviation of the gaussian blur applied to the wallpaper, in pixels
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub blur: Option<f32>,
+
+    /// Blend the wallpapers in linear colors, converting them from and to sRGB
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub srgb: Option<bool>,
+    /// Bits per color channel of the framebuffer, either 8 or 10
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "bit-depth")]
+    pub bit_depth: Option<u8>,
+    /// Premultiply the alpha of the wallpapers with transparency
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    #[serde(rename = "premultiply-alpha")]
+    pub premultiply_alpha: Option<bool>,
+
+    /// Draw a solid color or a gradient instead of an image, from 0.0 to 1.0 for each RGBA value
+    ///
+    /// See [crate::wallpaper_info::WallpaperInfo]
+    pub color: Option<[f32; 4]>,
+    pub gradient: Option<Gradient>,
 }
 
 impl SerializedWallpaperInfo {
+    fn color_source(&self) -> Result<Option<ColorSource>> {
+        Ok(match (self.path.is_some(), self.color, self.gradient) {
+            (_, None, None) => None,
+            (false, Some(color), None) => Some(ColorSource::Color(color)),
+            (false, None, Some(gradient)) => Some(ColorSource::Gradient(gradient)),
+            (_, _, _) => {
+                return Err(anyhow!(
+                    "only one of {}, {} and {} can be set",
+                    "path".bold().italic().blue(),
+                    "color".bold().italic().blue(),
+                    "gradient".bold().italic().blue(),
+                ))
+            }
+        })
+    }
+
     pub fn apply_and_validate(&self, default: &Self) -> Result<WallpaperInfo> {
+        let color_source = match (self.color_source()?, &self.path) {
+            (Some(color_source), _) => Some(color_source),
+            // This section draws an image, do not inherit the color from default
+            (None, Some(_)) => None,
+            (None, None) => default.color_source()?,
+        };
         let mut path_inherited = false;
+        let no_path = PathBuf::new();
         let path = match (&self.path, &default.path) {
             (Some(path), None) | (Some(path), Some(_))=> path,
             (None, Some(path)) => {
                 path_inherited = true;
                 path
             }
+            // There is no image to draw
+            (None, None) if color_source.is_some() => &no_path,
             (None, None) => {
                 return Err(anyhow!(
                     "attribute {} is not set",
@@ -82,7 +240,7 @@ impl SerializedWallpaperInfo {
         }
         .to_path_buf();
         // Ensure that a path exists
-        if !path.exists() {
+        if color_source.is_none() && !path.exists() {
             return Err(anyhow!(
                 "path {} for attribute {}{} does not exist",
                 path.to_string_lossy().italic().yellow(),
@@ -107,6 +265,7 @@ impl SerializedWallpaperInfo {
         let duration = match (&self.duration, &default.duration) {
             // duration is inherited from default, but this section set path to a file, ignore
             // duration
+            _ if color_source.is_some() => None,
             (None, Some(_)) if path.is_file() && !path_inherited => None,
             (Some(duration), _) | (None, Some(duration)) => Some(*duration),
             (None, None) => None,
@@ -129,6 +288,7 @@ impl SerializedWallpaperInfo {
         }
 
         let sorting = match (&self.sorting, &default.sorting) {
+            _ if color_source.is_some() => None,
             (None, Some(_)) if path.is_file() && !path_inherited => None,
             (Some(sorting), _) | (None, Some(sorting)) => Some(*sorting),
             (None, None) => None,
@@ -159,10 +319,18 @@ impl SerializedWallpaperInfo {
             (Some(size), _) | (None, Some(size)) => *size,
             (None, None) => ImagePicker::DEFAULT_DRAWN_IMAGES_QUEUE_SIZE,
         };
+        let texture_cache_size = match (&self.texture_cache_size, &default.texture_cache_size) {
+            (Some(size), _) | (None, Some(size)) => *size,
+            (None, None) => 0,
+        };
         let initial_transition = match (&self.initial_transition, &default.initial_transition) {
             (Some(initial_transition), _) | (None, Some(initial_transition)) => *initial_transition,
             (None, None) => true,
         };
+        let fade_from_black = match (&self.fade_from_black, &default.fade_from_black) {
+            (Some(fade_from_black), _) | (None, Some(fade_from_black)) => *fade_from_black,
+            (None, None) => false,
+        };
 
         let transition = match (&self.transition, &default.transition) {
             (Some(transition), _) | (None, Some(transition)) => transition.clone(),
@@ -174,11 +342,197 @@ impl SerializedWallpaperInfo {
             (None, None) => transition.default_transition_time(),
         };
 
+        let easing = match (&self.easing, &default.easing) {
+            (Some(easing), _) | (None, Some(easing)) => *easing,
+            (None, None) => Easing::default(),
+        };
+
+        let max_fps = match (&self.max_fps, &default.max_fps) {
+            (Some(max_fps), _) | (None, Some(max_fps)) => Some(*max_fps),
+            (None, None) => None,
+        };
+        // The frames are limited with an interval in milliseconds
+        ensure!(
+            max_fps.map_or(true, |max_fps| (1..=1000).contains(&max_fps)),
+            "{} must be between 1 and 1000",
+            "max-fps".bold().italic().blue(),
+        );
+        let swap_interval = match (&self.swap_interval, &default.swap_interval) {
+            (Some(swap_interval), _) | (None, Some(swap_interval)) => *swap_interval,
+            (None, None) => 1,
+        };
+        ensure!(
+            swap_interval >= 0,
+            "{} must be a positive number or 0",
+            "swap-interval".bold().italic().blue(),
+        );
+
         let offset = match (&self.offset, &default.offset) {
-            (Some(offset), _) | (None, Some(offset)) => Some(*offset),
+            (Some(offset), _) | (None, Some(offset)) => Some(offset.axes()),
             (None, None) => None,
         };
 
+        let flip_horizontal = match (&self.flip_horizontal, &default.flip_horizontal) {
+            (Some(flip_horizontal), _) | (None, Some(flip_horizontal)) => *flip_horizontal,
+            (None, None) => false,
+        };
+        let flip_vertical = match (&self.flip_vertical, &default.flip_vertical) {
+            (Some(flip_vertical), _) | (None, Some(flip_vertical)) => *flip_vertical,
+            (None, None) => false,
+        };
+
+        let tile_scale = match (&self.tile_scale, &default.tile_scale) {
+            (Some(tile_scale), _) | (None, Some(tile_scale)) => *tile_scale,
+            (None, None) => 1.0,
+        };
+        ensure!(
+            tile_scale > 0.0,
+            "{} must be a positive number",
+            "tile-scale".bold().italic().blue(),
+        );
+
+        let ken_burns_zoom = match (&self.ken_burns_zoom, &default.ken_burns_zoom) {
+            (Some(ken_burns_zoom), _) | (None, Some(ken_burns_zoom)) => *ken_burns_zoom,
+            (None, None) => 1.0,
+        };
+        ensure!(
+            ken_burns_zoom >= 1.0,
+            "{} must be greater or equal to 1.0",
+            "ken-burns-zoom".bold().italic().blue(),
+        );
+        let ken_burns_time = match (&self.ken_burns_time, &default.ken_burns_time) {
+            (Some(ken_burns_time), _) | (None, Some(ken_burns_time)) => *ken_burns_time,
+            (None, None) => WallpaperInfo::DEFAULT_KEN_BURNS_TIME,
+        };
+        ensure!(
+            ken_burns_time > 0,
+            "{} must be greater than 0",
+            "ken-burns-time".bold().italic().blue(),
+        );
+
+        let padding = match (&self.padding, &default.padding) {
+            (Some(padding), _) | (None, Some(padding)) => *padding,
+            (None, None) => 0,
+        };
+        let corner_radius = match (&self.corner_radius, &default.corner_radius) {
+            (Some(corner_radius), _) | (None, Some(corner_radius)) => *corner_radius,
+            (None, None) => 0,
+        };
+
+        let fill_color = match (&self.fill_color, &default.fill_color) {
+            (Some(fill_color), _) | (None, Some(fill_color)) => *fill_color,
+            (None, None) => WallpaperInfo::DEFAULT_FILL_COLOR,
+        };
+
+        let texture_filter = match (&self.texture_filter, &default.texture_filter) {
+            (Some(texture_filter), _) | (None, Some(texture_filter)) => *texture_filter,
+            (None, None) => TextureFilter::default(),
+        };
+        let wrap_mode = match (&self.wrap_mode, &default.wrap_mode) {
+            (Some(wrap_mode), _) | (None, Some(wrap_mode)) => Some(*wrap_mode),
+            (None, None) => None,
+        };
+
+        let anisotropy = match (&self.anisotropy, &default.anisotropy) {
+            (Some(anisotropy), _) | (None, Some(anisotropy)) => *anisotropy,
+            (None, None) => 1.0,
+        };
+        ensure!(
+            anisotropy >= 1.0,
+            "{} must be greater or equal to 1.0",
+            "anisotropy".bold().italic().blue(),
+        );
+        let mipmaps = match (&self.mipmaps, &default.mipmaps) {
+            (Some(mipmaps), _) | (None, Some(mipmaps)) => *mipmaps,
+            (None, None) => true,
+        };
+
+        let brightness = match (&self.brightness, &default.brightness) {
+            (Some(brightness), _) | (None, Some(brightness)) => *brightness,
+            (None, None) => 1.0,
+        };
+        let contrast = match (&self.contrast, &default.contrast) {
+            (Some(contrast), _) | (None, Some(contrast)) => *contrast,
+            (None, None) => 1.0,
+        };
+        let gamma = match (&self.gamma, &default.gamma) {
+            (Some(gamma), _) | (None, Some(gamma)) => *gamma,
+            (None, None) => 1.0,
+        };
+        let tint = match (&self.tint, &default.tint) {
+            (Some(tint), _) | (None, Some(tint)) => *tint,
+            (None, None) => [1.0, 1.0, 1.0],
+        };
+        ensure!(
+            tint.iter().all(|channel| *channel >= 0.0),
+            "{} must contain numbers greater or equal to 0",
+            "tint".bold().italic().blue(),
+        );
+        let vignette_strength = match (&self.vignette_strength, &default.vignette_strength) {
+            (Some(vignette_strength), _) | (None, Some(vignette_strength)) => *vignette_strength,
+            (None, None) => 0.0,
+        };
+        ensure!(
+            (0.0..=1.0).contains(&vignette_strength),
+            "{} must be between 0.0 and 1.0",
+            "vignette-strength".bold().italic().blue(),
+        );
+        let vignette_radius = match (&self.vignette_radius, &default.vignette_radius) {
+            (Some(vignette_radius), _) | (None, Some(vignette_radius)) => *vignette_radius,
+            (None, None) => 0.5,
+        };
+        ensure!(
+            (0.0..1.0).contains(&vignette_radius),
+            "{} must be between 0.0 and 1.0, excluding 1.0",
+            "vignette-radius".bold().italic().blue(),
+        );
+        let opacity = match (&self.opacity, &default.opacity) {
+            (Some(opacity), _) | (None, Some(opacity)) => *opacity,
+            (None, None) => 1.0,
+        };
+        ensure!(
+            (0.0..=1.0).contains(&opacity),
+            "{} must be between 0.0 and 1.0",
+            "opacity".bold().italic().blue(),
+        );
+        let srgb = match (&self.srgb, &default.srgb) {
+            (Some(srgb), _) | (None, Some(srgb)) => *srgb,
+            (None, None) => false,
+        };
+        let bit_depth = match (&self.bit_depth, &default.bit_depth) {
+            (Some(bit_depth), _) | (None, Some(bit_depth)) => *bit_depth,
+            (None, None) => 8,
+        };
+        ensure!(
+            bit_depth == 8 || bit_depth == 10,
+            "{} must be either 8 or 10",
+            "bit-depth".bold().italic().blue(),
+        );
+        let premultiply_alpha = match (&self.premultiply_alpha, &default.premultiply_alpha) {
+            (Some(premultiply_alpha), _) | (None, Some(premultiply_alpha)) => *premultiply_alpha,
+            (None, None) => false,
+        };
+        let blur = match (&self.blur, &default.blur) {
+            (Some(blur), _) | (None, Some(blur)) => *blur,
+            (None, None) => 0.0,
+        };
+        ensure!(
+            blur >= 0.0,
+            "{} must be greater or equal to 0",
+            "blur".bold().italic().blue(),
+        );
+        ensure!(
+            brightness >= 0.0 && contrast >= 0.0,
+            "{} and {} must be greater or equal to 0",
+            "brightness".bold().italic().blue(),
+            "contrast".bold().italic().blue(),
+        );
+        ensure!(
+            gamma > 0.0,
+            "{} must be greater than 0",
+            "gamma".bold().italic().blue(),
+        );
+
         Ok(WallpaperInfo {
             path,
             duration,
@@ -186,10 +540,39 @@ impl SerializedWallpaperInfo {
             sorting,
             mode,
             drawn_images_queue_size,
+            texture_cache_size,
             transition_time,
             initial_transition,
+            fade_from_black,
             transition,
+            easing,
+            max_fps,
+            swap_interval,
             offset,
+            flip_horizontal,
+            flip_vertical,
+            tile_scale,
+            ken_burns_zoom,
+            ken_burns_time,
+            padding,
+            corner_radius,
+            fill_color,
+            texture_filter,
+            wrap_mode,
+            anisotropy,
+            mipmaps,
+            brightness,
+            contrast,
+            gamma,
+            tint,
+            vignette_strength,
+            vignette_radius,
+            opacity,
+            blur,
+            srgb,
+            bit_depth,
+            premultiply_alpha,
+            color_source,
         })
     }
 }
@@ -312,14 +695,21 @@ impl PartialEq for Config {
 }
 
 pub fn tilde_expansion_deserialize<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    tilde_expansion_path_deserialize(deserializer).map(Some)
+}
+
+/// Like [tilde_expansion_deserialize], for the paths that are required
+pub fn tilde_expansion_path_deserialize<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let path = String::deserialize(deserializer)?;
     let path = Path::new(&path);
 
-    Ok(Some(
-        path.strip_prefix("~")
-            .map_or(path.to_path_buf(), |p| home_dir().unwrap().join(p)),
-    ))
+    Ok(path
+        .strip_prefix("~")
+        .map_or(path.to_path_buf(), |p| home_dir().unwrap().join(p)))
 }
@@ -1,16 +1,102 @@
-use std::{collections::HashMap, path::PathBuf, thread::JoinHandle};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
 
-use image::{open, RgbaImage};
+use color_eyre::{eyre::ensure, Result};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    imageops, open, AnimationDecoder, DynamicImage, Frames, ImageBuffer, ImageFormat, Rgba,
+    RgbaImage,
+};
 use log::warn;
 
+use crate::render::premultiply_alpha;
+
 struct Image {
-    data: Option<RgbaImage>,
-    thread_handle: Option<JoinHandle<Option<RgbaImage>>>,
+    data: Option<ImageData>,
+    thread_handle: Option<JoinHandle<Option<ImageData>>>,
     requesters: Vec<String>,
 }
 
+/// A decoded image, ready to be loaded into a texture
+#[derive(Clone)]
+pub struct ImageData {
+    pub frames: ImageFrames,
+    /// Size of the image file, the frames are smaller when they have been scaled down to fit
+    /// in a texture
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An image with 16 bits per channel
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+#[derive(Clone)]
+pub enum ImageFrames {
+    Static(RgbaImage),
+    /// An opaque image with more than 8 bits per channel, loaded in a 10-bit texture when the
+    /// display has a 10-bit framebuffer
+    Deep(Rgba16Image),
+    /// An animated image (GIF or APNG), each frame comes with its delay
+    Animated(Vec<(RgbaImage, Duration)>),
+}
+
+impl ImageData {
+    pub fn new(frames: ImageFrames) -> Self {
+        let (width, height) = match &frames {
+            ImageFrames::Static(image) => image.dimensions(),
+            ImageFrames::Deep(image) => image.dimensions(),
+            ImageFrames::Animated(frames) => frames[0].0.dimensions(),
+        };
+        Self {
+            frames,
+            width,
+            height,
+        }
+    }
+
+    /// Scale down the frames that do not fit in a texture of `max_size`, the size of the
+    /// image is kept
+    fn downscale(self, max_size: u32) -> Self {
+        let frames = match self.frames {
+            ImageFrames::Static(image) => ImageFrames::Static(downscale(image, max_size)),
+            ImageFrames::Deep(image) => ImageFrames::Deep(downscale_deep(image, max_size)),
+            ImageFrames::Animated(frames) => ImageFrames::Animated(
+                frames
+                    .into_iter()
+                    .map(|(frame, delay)| (downscale(frame, max_size), delay))
+                    .collect(),
+            ),
+        };
+        Self { frames, ..self }
+    }
+}
+
+/// Cheap token identifying the content of an image file, without reading it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageIdentity {
+    path: PathBuf,
+    modified: SystemTime,
+}
+
+impl ImageIdentity {
+    /// Returns None when the modification time of the file is not available
+    pub fn new(path: &Path) -> Option<Self> {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        Some(Self {
+            path: path.to_path_buf(),
+            modified,
+        })
+    }
+}
+
 pub enum ImageLoaderStatus {
-    Loaded(RgbaImage),
+    Loaded(ImageData),
     Waiting,
     Error,
 }
@@ -26,7 +112,15 @@ impl ImageLoader {
         }
     }
 
-    pub fn background_load(&mut self, path: PathBuf, requester_name: String) -> ImageLoaderStatus {
+    /// Decode the image in `path` in another thread, scaling it down to `max_size` when it
+    /// does not fit in a texture. The surfaces requesting the same image share the size of
+    /// the first one, they are all drawn by the same GPU
+    pub fn background_load(
+        &mut self,
+        path: PathBuf,
+        requester_name: String,
+        max_size: u32,
+    ) -> ImageLoaderStatus {
         if let Some(image) = self.images.get_mut(&path) {
             if let Some(handle) = image.thread_handle.take() {
                 if handle.is_finished() {
@@ -77,8 +171,8 @@ impl ImageLoader {
         } else {
             // Start loading a new image
             let path_clone = path.clone();
-            let handle = std::thread::spawn(|| match open(path_clone) {
-                Ok(image) => Some(image.into_rgba8()),
+            let handle = std::thread::spawn(move || match decode_image(&path_clone) {
+                Ok(image) => Some(image.downscale(max_size)),
                 Err(err) => {
                     warn!("{err:?}");
                     None
@@ -103,3 +197,125 @@ impl ImageLoader {
             .any(|(_, image)| { image.requesters.is_empty() }));
     }
 }
+
+fn decode_image(path: &Path) -> Result<ImageData> {
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(BufReader::new(File::open(path)?))?;
+            collect_frames(decoder.into_frames())
+        }
+        Ok(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(BufReader::new(File::open(path)?))?;
+            if decoder.is_apng()? {
+                collect_frames(decoder.apng()?.into_frames())
+            } else {
+                Ok(ImageData::new(static_frames(DynamicImage::from_decoder(
+                    decoder,
+                )?)))
+            }
+        }
+        _ => Ok(ImageData::new(static_frames(open(path)?))),
+    }
+}
+
+/// Keep the opaque images with more than 8 bits per channel, they can be loaded in a 10-bit
+/// texture
+fn static_frames(image: DynamicImage) -> ImageFrames {
+    let color = image.color();
+    if color.bytes_per_pixel() <= color.channel_count() {
+        return ImageFrames::Static(image.into_rgba8());
+    }
+    let image = image.into_rgba16();
+    if image.pixels().all(|pixel| pixel[3] == u16::MAX) {
+        ImageFrames::Deep(image)
+    } else {
+        // The 10-bit textures only keep 2 bits of alpha
+        ImageFrames::Static(DynamicImage::ImageRgba16(image).into_rgba8())
+    }
+}
+
+fn collect_frames(frames: Frames) -> Result<ImageData> {
+    let mut frames: Vec<(RgbaImage, Duration)> = frames
+        .map(|frame| {
+            frame.map(|frame| {
+                let delay = frame.delay().into();
+                (frame.into_buffer(), delay)
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    ensure!(!frames.is_empty(), "the animated image has no frames");
+    // An animation with a single frame is just a static image
+    Ok(ImageData::new(if frames.len() == 1 {
+        ImageFrames::Static(frames.remove(0).0)
+    } else {
+        ImageFrames::Animated(frames)
+    }))
+}
+
+/// Size of an image scaled down to fit in a texture, keeping its ratio. Returns None when
+/// the image already fits
+fn downscaled_size((width, height): (u32, u32), max_size: u32) -> Option<(u32, u32)> {
+    if width <= max_size && height <= max_size {
+        return None;
+    }
+    let scale = max_size as f32 / width.max(height) as f32;
+    let new_width = ((width as f32 * scale) as u32).clamp(1, max_size);
+    let new_height = ((height as f32 * scale) as u32).clamp(1, max_size);
+    warn!(
+        "the image is {width}x{height}, bigger than the maximum texture size {max_size}; \
+        scaling it down to {new_width}x{new_height}"
+    );
+    Some((new_width, new_height))
+}
+
+/// Scale down the image when it does not fit in a texture
+fn downscale(image: RgbaImage, max_size: u32) -> RgbaImage {
+    let Some((new_width, new_height)) = downscaled_size(image.dimensions(), max_size) else {
+        return image;
+    };
+    // Resize the premultiplied colors, otherwise the colors of the transparent pixels would
+    // bleed into their neighbours
+    match premultiply_alpha(&image) {
+        Some(premultiplied) => unpremultiply_alpha(imageops::resize(
+            &premultiplied,
+            new_width,
+            new_height,
+            imageops::FilterType::Triangle,
+        )),
+        None => imageops::resize(
+            &image,
+            new_width,
+            new_height,
+            imageops::FilterType::Triangle,
+        ),
+    }
+}
+
+/// Scale down the deep image when it does not fit in a texture, it is opaque hence there are
+/// no colors to premultiply
+fn downscale_deep(image: Rgba16Image, max_size: u32) -> Rgba16Image {
+    match downscaled_size(image.dimensions(), max_size) {
+        Some((new_width, new_height)) => imageops::resize(
+            &image,
+            new_width,
+            new_height,
+            imageops::FilterType::Triangle,
+        ),
+        None => image,
+    }
+}
+
+/// Divide the colors of the image by their alpha, the renderer premultiplies them again when
+/// needed
+fn unpremultiply_alpha(mut image: RgbaImage) -> RgbaImage {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            continue;
+        }
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+    image
+}
@@ -107,6 +107,10 @@ pub fn handle_message(
         IpcMessage::PreviousWallpaper { monitors } => {
             check_monitors(wpaperd, &monitors).map(|_| {
                 for surface in collect_surfaces(wpaperd, monitors) {
+                    // There are no images to cycle while a color is drawn
+                    if surface.wallpaper_info.color_source.is_some() {
+                        continue;
+                    }
                     surface.image_picker.previous_image();
                     surface.queue_draw(&qh);
                 }
@@ -117,6 +121,9 @@ pub fn handle_message(
 
         IpcMessage::NextWallpaper { monitors } => check_monitors(wpaperd, &monitors).map(|_| {
             for surface in collect_surfaces(wpaperd, monitors) {
+                if surface.wallpaper_info.color_source.is_some() {
+                    continue;
+                }
                 surface
                     .image_picker
                     .next_image(&surface.wallpaper_info.path, &qh);
@@ -149,6 +156,23 @@ pub fn handle_message(
             IpcResponse::Ok
         }),
 
+        IpcMessage::CaptureWallpaper { monitor, path } => wpaperd
+            .surfaces
+            .iter_mut()
+            .find(|surface| surface.name() == monitor)
+            .ok_or_else(|| IpcError::MonitorNotFound {
+                monitor: monitor.clone(),
+            })
+            .and_then(|surface| {
+                surface
+                    .capture(&path)
+                    .map(|_| IpcResponse::Ok)
+                    .map_err(|err| IpcError::CaptureError {
+                        monitor,
+                        error: format!("{err:?}"),
+                    })
+            }),
+
         IpcMessage::TogglePauseWallpaper { monitors } => {
             check_monitors(wpaperd, &monitors).map(|_| {
                 for surface in collect_surfaces(wpaperd, monitors) {
@@ -138,16 +138,18 @@ fn run(opts: Opts, xdg_dirs: BaseDirectories) -> Result<()> {
         egl_display,
         filelist_cache.clone(),
         groups,
+        event_loop.handle(),
     )?;
 
     // Start listening on the IPC socket
     let socket = listen_on_ipc_socket(&socket_path()?).context("spawning the ipc socket")?;
 
     // Add source to calloop loop.
+    let ipc_qh = qh.clone();
     event_loop
         .handle()
-        .insert_source(socket, |stream, _, wpaperd| {
-            if let Err(err) = handle_message(stream, qh.clone(), wpaperd) {
+        .insert_source(socket, move |stream, _, wpaperd| {
+            if let Err(err) = handle_message(stream, ipc_qh.clone(), wpaperd) {
                 error!("{:?}", err);
             }
         })?;
@@ -0,0 +1,179 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use image::RgbaImage;
+
+/// Browsers show the frames without a delay for 100 milliseconds, do the same
+const DEFAULT_FRAME_DELAY: u32 = 100;
+
+/// The frames of an animated wallpaper, played in a loop
+pub struct Animation {
+    /// Each frame with the milliseconds it should be shown for
+    frames: Vec<(RgbaImage, u32)>,
+    current_frame: usize,
+    /// Time (in milliseconds) when the current frame has been shown; it is only known
+    /// after the first frame callback
+    frame_started: Option<u32>,
+    /// Duration of an entire loop of the animation
+    loop_time: u32,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<(RgbaImage, Duration)>) -> Self {
+        let frames: Vec<(RgbaImage, u32)> = frames
+            .into_iter()
+            .map(|(frame, delay)| {
+                let delay = match delay.as_millis() {
+                    0 => DEFAULT_FRAME_DELAY,
+                    delay => delay.try_into().unwrap_or(u32::MAX),
+                };
+                (frame, delay)
+            })
+            .collect();
+        let loop_time = frames
+            .iter()
+            .fold(0u32, |acc, (_, delay)| acc.saturating_add(*delay));
+
+        Self {
+            frames,
+            current_frame: 0,
+            frame_started: None,
+            loop_time,
+        }
+    }
+
+    #[inline]
+    pub fn current_frame(&self) -> &RgbaImage {
+        &self.frames[self.current_frame].0
+    }
+
+    /// Returns the time when the next frame has to be shown, None before the first frame
+    /// callback
+    #[inline]
+    pub fn next_frame_time(&self) -> Option<u32> {
+        self.frame_started
+            .map(|frame_started| frame_started.saturating_add(self.frames[self.current_frame].1))
+    }
+
+    /// Advance the animation up to `time`. Returns true if the frame to show has changed
+    pub fn advance(&mut self, time: u32) -> bool {
+        let Some(frame_started) = self.frame_started else {
+            // The first frame is being shown right now
+            self.frame_started = Some(time);
+            return false;
+        };
+
+        let previous_frame = self.current_frame;
+        // Skip the entire loops that we have missed, i.e. when the display was turned off
+        let mut elapsed = time.saturating_sub(frame_started) % self.loop_time;
+        while elapsed >= self.frames[self.current_frame].1 {
+            elapsed -= self.frames[self.current_frame].1;
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+        }
+        // Keep track of the time already spent on the frame, the next one might
+        // need to be shown sooner
+        self.frame_started = Some(time - elapsed);
+
+        previous_frame != self.current_frame
+    }
+}
+
+/// Slow zoom and pan of a static wallpaper (Ken Burns effect), going back and forth
+pub struct KenBurns {
+    /// Zoom reached in the middle of the cycle
+    zoom: f32,
+    /// Milliseconds needed to zoom in and out again
+    cycle_time: u32,
+    /// Time (in milliseconds) of the first frame, known after the first frame callback
+    started: Option<u32>,
+    /// Time of the last update, to limit the redraws like the transitions
+    last_update: Option<u32>,
+    /// Goes from 0.0 to 1.0 and back during a cycle
+    progress: f32,
+}
+
+impl KenBurns {
+    pub fn new(zoom: f32, cycle_time: u32) -> Self {
+        Self {
+            zoom,
+            cycle_time,
+            started: None,
+            last_update: None,
+            progress: 0.0,
+        }
+    }
+
+    /// Advance the effect up to `time`, unless the last update happened less than
+    /// `frame_interval` milliseconds ago. Returns true if the wallpaper has moved
+    pub fn advance(&mut self, time: u32, frame_interval: u32) -> bool {
+        if let Some(last_update) = self.last_update {
+            if time.saturating_sub(last_update) < frame_interval {
+                return false;
+            }
+        }
+        self.last_update = Some(time);
+        let started = *self.started.get_or_insert(time);
+        let phase =
+            (time.saturating_sub(started) % self.cycle_time) as f32 / self.cycle_time as f32;
+        // Ease in and out at both ends of the cycle
+        let progress = 0.5 - 0.5 * (phase * TAU).cos();
+        let moved = progress != self.progress;
+        self.progress = progress;
+        moved
+    }
+
+    /// Returns the time of the next update allowed by `frame_interval`, None when it can
+    /// happen at any frame
+    #[inline]
+    pub fn next_update_time(&self, frame_interval: u32) -> Option<u32> {
+        if frame_interval == 0 {
+            return None;
+        }
+        self.last_update
+            .map(|last_update| last_update.saturating_add(frame_interval))
+    }
+
+    #[inline]
+    pub fn zoom(&self) -> f32 {
+        1.0 + (self.zoom - 1.0) * self.progress
+    }
+
+    /// Pan across the image while zooming, starting from `offset` when the image is not
+    /// zoomed and moving towards the farthest side, so that the offset stays inside of it
+    pub fn offset(&self, offset: [f32; 2]) -> [f32; 2] {
+        offset.map(|offset| {
+            let direction = if offset <= 0.5 { 1.0 } else { -1.0 };
+            (offset + direction * self.progress * 0.25).clamp(0.0, 1.0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ken_burns_cycle() {
+        let mut ken_burns = KenBurns::new(2.0, 1000);
+        // The cycle starts with the first frame, without zoom nor pan
+        assert!(!ken_burns.advance(100, 0));
+        assert_eq!(ken_burns.zoom(), 1.0);
+        assert_eq!(ken_burns.offset([0.5, 0.2]), [0.5, 0.2]);
+
+        // The middle of the cycle is the most zoomed in
+        assert!(ken_burns.advance(600, 0));
+        assert_eq!(ken_burns.zoom(), 2.0);
+        assert_eq!(ken_burns.offset([0.5, 0.9]), [0.75, 0.65]);
+        // The image moves even when it is anchored to its edges
+        assert_eq!(ken_burns.offset([0.0, 1.0]), [0.25, 0.75]);
+
+        // Updates are limited by the frame interval
+        assert!(!ken_burns.advance(650, 100));
+        assert_eq!(ken_burns.next_update_time(100), Some(700));
+        assert!(ken_burns.advance(700, 100));
+        assert!(ken_burns.zoom() < 2.0);
+
+        // And the effect goes back to the start at the end of the cycle
+        ken_burns.advance(1100, 0);
+        assert_eq!(ken_burns.zoom(), 1.0);
+    }
+}
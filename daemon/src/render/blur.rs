@@ -0,0 +1,242 @@
+use std::ffi::CStr;
+
+use color_eyre::{
+    eyre::{bail, ensure, Context},
+    Result,
+};
+
+use log::warn;
+
+use crate::gl_check;
+
+use super::{
+    gl,
+    shader::{create_shader, BLUR_FRAGMENT_SHADER_SOURCE, BLUR_VERTEX_SHADER_SOURCE},
+};
+
+/// Separable gaussian blur applied to the wallpaper after it has been drawn.
+///
+/// The wallpaper is drawn into the first framebuffer, then blurred horizontally into the
+/// second one and finally blurred vertically into the default framebuffer.
+pub struct Blur {
+    program: gl::types::GLuint,
+    framebuffers: [gl::types::GLuint; 2],
+    textures: [gl::types::GLuint; 2],
+    width: i32,
+    height: i32,
+    /// the intermediate textures store half floats instead of 8 bits per channel
+    half_float: bool,
+}
+
+impl Blur {
+    pub fn new(gl: &gl::Gl, width: i32, height: i32, half_float: bool) -> Result<Self> {
+        let program = create_blur_program(gl).context("unable to create the blur program")?;
+        let mut blur = Self {
+            program,
+            framebuffers: [0; 2],
+            textures: [0; 2],
+            width: 0,
+            height: 0,
+            half_float,
+        };
+        unsafe {
+            gl.GenFramebuffers(2, blur.framebuffers.as_mut_ptr());
+            gl.GenTextures(2, blur.textures.as_mut_ptr());
+        }
+        // Do not leak the objects created so far
+        if let Err(err) = blur.init(gl, width, height) {
+            blur.delete(gl);
+            return Err(err);
+        }
+
+        Ok(blur)
+    }
+
+    fn init(&mut self, gl: &gl::Gl, width: i32, height: i32) -> Result<()> {
+        unsafe {
+            gl_check!(gl, "generating the framebuffers and the blur textures");
+
+            // Use a texture unit that is not used by the wallpapers
+            gl.ActiveTexture(gl::TEXTURE2);
+            gl_check!(gl, "activating textures");
+            for (framebuffer, texture) in self.framebuffers.iter().zip(self.textures.iter()) {
+                gl.BindTexture(gl::TEXTURE_2D, *texture);
+                gl_check!(gl, "binding the blur texture");
+                for (param, value) in [
+                    (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+                    (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                    (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                    (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                ] {
+                    gl.TexParameteri(gl::TEXTURE_2D, param, value as i32);
+                    gl_check!(gl, "defining the blur texture parameters");
+                }
+                gl.BindFramebuffer(gl::FRAMEBUFFER, *framebuffer);
+                gl_check!(gl, "binding the framebuffer");
+                gl.FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_2D,
+                    *texture,
+                    0,
+                );
+                gl_check!(gl, "attaching the texture to the framebuffer");
+            }
+            gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl_check!(gl, "binding the default framebuffer");
+        }
+
+        self.resize(gl, width, height)
+    }
+
+    /// Resize the intermediate textures to the size of the display
+    pub fn resize(&mut self, gl: &gl::Gl, width: i32, height: i32) -> Result<()> {
+        self.width = width;
+        self.height = height;
+        unsafe {
+            gl.ActiveTexture(gl::TEXTURE2);
+            gl_check!(gl, "activating textures");
+            for (framebuffer, texture) in self.framebuffers.iter().zip(self.textures.iter()) {
+                gl.BindTexture(gl::TEXTURE_2D, *texture);
+                gl_check!(gl, "binding the blur texture");
+                let (internal_format, texel_type) = if self.half_float {
+                    (gl::RGBA16F, gl::HALF_FLOAT)
+                } else {
+                    (gl::RGBA, gl::UNSIGNED_BYTE)
+                };
+                gl.TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    internal_format.try_into().unwrap(),
+                    width,
+                    height,
+                    0,
+                    gl::RGBA,
+                    texel_type,
+                    std::ptr::null(),
+                );
+                gl_check!(gl, "defining the blur texture");
+                gl.BindFramebuffer(gl::FRAMEBUFFER, *framebuffer);
+                gl_check!(gl, "binding the framebuffer");
+                let status = gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+                gl_check!(gl, "checking the framebuffer status");
+                if status != gl::FRAMEBUFFER_COMPLETE && self.half_float {
+                    // Not an error, the blur only bands a bit more with 8 bits per channel
+                    warn!(
+                        "unable to render to half float textures, blurring with 8 bits per channel"
+                    );
+                    self.half_float = false;
+                    return self.resize(gl, width, height);
+                }
+                ensure!(
+                    status == gl::FRAMEBUFFER_COMPLETE,
+                    "the blur framebuffer is not complete: {status}"
+                );
+            }
+            gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl_check!(gl, "binding the default framebuffer");
+            gl.ActiveTexture(gl::TEXTURE1);
+            gl_check!(gl, "activating textures");
+        }
+
+        Ok(())
+    }
+
+    /// Bind the framebuffer where the wallpaper must be drawn before calling [Blur::draw]
+    pub unsafe fn bind_framebuffer(&self, gl: &gl::Gl) -> Result<()> {
+        gl.BindFramebuffer(gl::FRAMEBUFFER, self.framebuffers[0]);
+        gl_check!(gl, "binding the framebuffer");
+
+        Ok(())
+    }
+
+    /// Blur the wallpaper into the default framebuffer. The caller has to switch back
+    /// to its own program afterwards
+    pub unsafe fn draw(&self, gl: &gl::Gl, sigma: f32) -> Result<()> {
+        gl.UseProgram(self.program);
+        gl_check!(gl, "calling UseProgram");
+        gl.ActiveTexture(gl::TEXTURE2);
+        gl_check!(gl, "activating textures");
+
+        let loc = gl.GetUniformLocation(self.program, c"sigma".as_ptr());
+        gl_check!(gl, "getting the uniform location for sigma");
+        gl.Uniform1f(loc, sigma);
+        gl_check!(gl, "calling Uniform1f");
+        let direction_loc = gl.GetUniformLocation(self.program, c"direction".as_ptr());
+        gl_check!(gl, "getting the uniform location for direction");
+
+        let passes = [
+            (
+                self.textures[0],
+                self.framebuffers[1],
+                [1.0 / self.width as f32, 0.0],
+            ),
+            (self.textures[1], 0, [0.0, 1.0 / self.height as f32]),
+        ];
+        for (texture, framebuffer, direction) in passes {
+            gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl_check!(gl, "binding the framebuffer");
+            gl.BindTexture(gl::TEXTURE_2D, texture);
+            gl_check!(gl, "binding the blur texture");
+            gl.Uniform2fv(direction_loc, 1, direction.as_ptr());
+            gl_check!(gl, "calling Uniform2fv");
+            gl.DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null());
+            gl_check!(gl, "drawing the triangles");
+        }
+
+        gl.ActiveTexture(gl::TEXTURE1);
+        gl_check!(gl, "activating textures");
+
+        Ok(())
+    }
+
+    pub fn delete(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.DeleteFramebuffers(2, self.framebuffers.as_ptr());
+            gl.DeleteTextures(2, self.textures.as_ptr());
+            gl.DeleteProgram(self.program);
+        }
+    }
+}
+
+fn create_blur_program(gl: &gl::Gl) -> Result<gl::types::GLuint> {
+    unsafe {
+        let program = gl.CreateProgram();
+        gl_check!(gl, "calling CreateProgram");
+
+        let vertex_shader =
+            create_shader(gl, gl::VERTEX_SHADER, &[BLUR_VERTEX_SHADER_SOURCE.as_ptr()])
+                .context("unable to create the blur vertex shader")?;
+        let fragment_shader = create_shader(
+            gl,
+            gl::FRAGMENT_SHADER,
+            &[BLUR_FRAGMENT_SHADER_SOURCE.as_ptr()],
+        )
+        .context("unable to create the blur fragment shader")?;
+
+        gl.AttachShader(program, vertex_shader);
+        gl_check!(gl, "attach vertex shader");
+        gl.AttachShader(program, fragment_shader);
+        gl_check!(gl, "attach fragment shader");
+        gl.LinkProgram(program);
+        gl_check!(gl, "linking the program");
+        let mut status: i32 = 0;
+        gl.GetProgramiv(program, gl::LINK_STATUS, &mut status as *mut _);
+        ensure!(status == 1, "Program was not linked correctly");
+        gl.DeleteShader(vertex_shader);
+        gl_check!(gl, "deleting the vertex shader");
+        gl.DeleteShader(fragment_shader);
+        gl_check!(gl, "deleting the fragment shader");
+
+        // The texture to blur is always bound to TEXTURE2
+        gl.UseProgram(program);
+        gl_check!(gl, "calling UseProgram");
+        let loc = gl.GetUniformLocation(program, c"u_texture".as_ptr());
+        gl_check!(gl, "getting the uniform location for u_texture");
+        ensure!(loc >= 0, "u_texture not found");
+        gl.Uniform1i(loc, 2);
+        gl_check!(gl, "calling Uniform1i");
+
+        Ok(program)
+    }
+}
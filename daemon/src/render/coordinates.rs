@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Coordinates {
     x_left: f32,
     x_right: f32,
@@ -43,6 +44,51 @@ impl Coordinates {
             y_top: Self::TEX_Y_TOP,
         }
     }
+
+    /// Apply the scale of the texture coordinates around `offset`, like the fragment
+    /// shader does when sampling the wallpaper
+    pub fn scale(self, scale: [f32; 2], offset: [f32; 2]) -> Self {
+        let [scale_x, scale_y] = scale;
+        let [offset_x, offset_y] = offset;
+        Self {
+            x_left: (self.x_left - offset_x) * scale_x + offset_x,
+            x_right: (self.x_right - offset_x) * scale_x + offset_x,
+            y_bottom: (self.y_bottom - offset_y) * scale_y + offset_y,
+            y_top: (self.y_top - offset_y) * scale_y + offset_y,
+        }
+    }
+
+    /// The bottom left corner of the coordinates, which the fragment shader maps to the one
+    /// of the display
+    pub fn origin(&self) -> [f32; 2] {
+        [self.x_left, self.y_bottom]
+    }
+
+    /// Width and height of the coordinates, the scale of the texture coordinates in the
+    /// fragment shader
+    pub fn size(&self) -> [f32; 2] {
+        [self.x_right - self.x_left, self.y_top - self.y_bottom]
+    }
+
+    /// Mirror the coordinates horizontally and/or vertically
+    pub fn flip(self, horizontal: bool, vertical: bool) -> Self {
+        let (x_left, x_right) = if horizontal {
+            (self.x_right, self.x_left)
+        } else {
+            (self.x_left, self.x_right)
+        };
+        let (y_bottom, y_top) = if vertical {
+            (self.y_top, self.y_bottom)
+        } else {
+            (self.y_bottom, self.y_top)
+        };
+        Self {
+            x_left,
+            x_right,
+            y_bottom,
+            y_top,
+        }
+    }
 }
 
 pub fn get_opengl_point_coordinates(
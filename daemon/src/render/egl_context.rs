@@ -1,9 +1,17 @@
+use std::{collections::HashMap, rc::Rc};
+
 use smithay_client_toolkit::reexports::client::{protocol::wl_surface::WlSurface, Proxy};
 use wayland_egl::WlEglSurface;
 
 use egl::API as egl;
 
-use color_eyre::{eyre::Context, Result};
+use color_eyre::{
+    eyre::{Context, ContextCompat},
+    Result,
+};
+use log::warn;
+
+use super::ShareGroup;
 
 pub struct EglContext {
     pub display: egl::Display,
@@ -11,57 +19,220 @@ pub struct EglContext {
     pub config: egl::Config,
     wl_egl_surface: WlEglSurface,
     surface: khronos_egl::Surface,
+    /// the surface converts the linear colors to sRGB
+    srgb: bool,
+    /// the configuration has 10 bits per channel
+    deep_color: bool,
+    /// the openGL objects shared with the other contexts, or only used by this one when it
+    /// could not join a share group
+    share_group: Rc<ShareGroup>,
+}
+
+const SRGB_SURFACE_ATTRIBUTES: [i32; 3] = [egl::GL_COLORSPACE, egl::GL_COLORSPACE_SRGB, egl::NONE];
+
+const ATTRIBUTES: [i32; 7] = [
+    egl::RED_SIZE,
+    8,
+    egl::GREEN_SIZE,
+    8,
+    egl::BLUE_SIZE,
+    8,
+    egl::NONE,
+];
+
+const CONTEXT_ATTRIBUTES: [i32; 5] = [
+    egl::CONTEXT_MAJOR_VERSION,
+    2,
+    egl::CONTEXT_MINOR_VERSION,
+    0,
+    egl::NONE,
+];
+
+/// Contexts shared by the contexts of all the displays, so that the displays using the same
+/// EGL configuration are in the same share group and the openGL objects of a display can be
+/// used by the other ones. They are never made current, hence they do not need a surface
+pub struct ShareContexts {
+    display: egl::Display,
+    /// None when the share context for that configuration could not be created
+    contexts: HashMap<egl::Config, Option<(egl::Context, Rc<ShareGroup>)>>,
+}
+
+impl ShareContexts {
+    pub fn new(display: egl::Display) -> Self {
+        Self {
+            display,
+            contexts: HashMap::new(),
+        }
+    }
+
+    /// Returns the share context compatible with `config` and the objects of its group,
+    /// created the first time it is requested
+    fn get(&mut self, config: egl::Config) -> Option<(egl::Context, Rc<ShareGroup>)> {
+        let display = self.display;
+        self.contexts
+            .entry(config)
+            .or_insert_with(|| {
+                match egl
+                    .create_context(display, config, None, &CONTEXT_ATTRIBUTES)
+                    .context("unable to create the shared EGL context")
+                {
+                    Ok(context) => Some((context, Rc::default())),
+                    Err(err) => {
+                        // Each display can still have its own context
+                        warn!("{err:?}");
+                        None
+                    }
+                }
+            })
+            .clone()
+    }
+}
+
+impl Drop for ShareContexts {
+    fn drop(&mut self) {
+        // The objects of the share group are kept until its last context is destroyed
+        for (context, _) in self.contexts.values().flatten() {
+            if let Err(err) = egl.destroy_context(self.display, *context) {
+                warn!("unable to destroy the shared EGL context: {err:?}");
+            }
+        }
+    }
+}
+
+/// Create the EGL surface of `wl_egl_surface`, falling back to the default colorspace when
+/// the sRGB one is not supported. Returns the surface and whether it uses sRGB
+fn create_window_surface(
+    egl_display: egl::Display,
+    config: egl::Config,
+    wl_egl_surface: &WlEglSurface,
+    srgb: bool,
+) -> Result<(egl::Surface, bool)> {
+    let create = |attributes: Option<&[i32]>| unsafe {
+        egl.create_window_surface(
+            egl_display,
+            config,
+            wl_egl_surface.ptr() as egl::NativeWindowType,
+            attributes,
+        )
+        .context("unable to create an EGL surface")
+    };
+    if srgb {
+        let supported = egl
+            .query_string(Some(egl_display), egl::EXTENSIONS)
+            .map(|extensions| {
+                extensions
+                    .to_string_lossy()
+                    .split_whitespace()
+                    .any(|extension| extension == "EGL_KHR_gl_colorspace")
+            })
+            .unwrap_or(false);
+        match supported.then(|| create(Some(&SRGB_SURFACE_ATTRIBUTES))) {
+            Some(Ok(surface)) => return Ok((surface, true)),
+            Some(Err(err)) => warn!("{err:?}"),
+            None => {}
+        }
+        // Not an error, the wallpaper is blended in sRGB colors instead
+        warn!(
+            "the sRGB colorspace is not supported, blending the wallpapers without converting them"
+        );
+    }
+
+    Ok((create(None)?, false))
 }
 
 impl EglContext {
-    pub fn new(egl_display: egl::Display, wl_surface: &WlSurface) -> Self {
-        const ATTRIBUTES: [i32; 7] = [
+    pub fn new(
+        egl_display: egl::Display,
+        wl_surface: &WlSurface,
+        srgb: bool,
+        bit_depth: u8,
+        share_contexts: &mut ShareContexts,
+    ) -> Result<Self> {
+        const DEEP_COLOR_ATTRIBUTES: [i32; 9] = [
             egl::RED_SIZE,
-            8,
+            10,
             egl::GREEN_SIZE,
-            8,
+            10,
             egl::BLUE_SIZE,
-            8,
-            egl::NONE,
-        ];
-
-        let config = egl
-            .choose_first_config(egl_display, &ATTRIBUTES)
-            .expect("unable to choose an EGL configuration")
-            .expect("no EGL configuration found");
-
-        const CONTEXT_ATTRIBUTES: [i32; 5] = [
-            egl::CONTEXT_MAJOR_VERSION,
+            10,
+            egl::ALPHA_SIZE,
             2,
-            egl::CONTEXT_MINOR_VERSION,
-            0,
             egl::NONE,
         ];
 
-        let context = egl
-            .create_context(egl_display, config, None, &CONTEXT_ATTRIBUTES)
-            .expect("unable to create an EGL context");
+        let deep_color_config = if bit_depth == 10 {
+            let config = egl
+                .choose_first_config(egl_display, &DEEP_COLOR_ATTRIBUTES)
+                .ok()
+                .flatten();
+            if config.is_none() {
+                // Not an error, the 8-bit configuration works everywhere
+                warn!("no 10-bit EGL configuration found, using 8 bits per channel");
+            }
+            config
+        } else {
+            None
+        };
+        let deep_color = deep_color_config.is_some();
+        let config = match deep_color_config {
+            Some(config) => config,
+            None => egl
+                .choose_first_config(egl_display, &ATTRIBUTES)
+                .context("unable to choose an EGL configuration")?
+                .context("no EGL configuration found")?,
+        };
 
-        // First, create a small surface, we don't know the size of the output yet
-        let wl_egl_surface = WlEglSurface::new(wl_surface.id(), 10, 10).unwrap();
-
-        let surface = unsafe {
-            egl.create_window_surface(
-                egl_display,
-                config,
-                wl_egl_surface.ptr() as egl::NativeWindowType,
-                None,
-            )
-            .expect("unable to create an EGL surface")
+        let create_context = |share_context| {
+            egl.create_context(egl_display, config, share_context, &CONTEXT_ATTRIBUTES)
+                .context("unable to create an EGL context")
+        };
+        let (context, share_group) = match share_contexts.get(config) {
+            Some((share_context, share_group)) => match create_context(Some(share_context)) {
+                Ok(context) => (context, share_group),
+                Err(err) => {
+                    // Not an error, the display does not share its objects with the other ones
+                    warn!("{err:?}");
+                    (create_context(None)?, Rc::default())
+                }
+            },
+            None => (create_context(None)?, Rc::default()),
         };
 
-        Self {
+        // First, create a small surface, we don't know the size of the output yet
+        let wl_egl_surface = WlEglSurface::new(wl_surface.id(), 10, 10)
+            .context("unable to create a WlEglSurface")?;
+
+        let (surface, srgb) = create_window_surface(egl_display, config, &wl_egl_surface, srgb)?;
+
+        Ok(Self {
             display: egl_display,
             context,
             config,
             surface,
             wl_egl_surface,
-        }
+            srgb,
+            deep_color,
+            share_group,
+        })
+    }
+
+    /// Returns true when the surface converts the linear colors to sRGB, which might not be
+    /// supported even if it has been requested
+    #[inline]
+    pub fn srgb(&self) -> bool {
+        self.srgb
+    }
+
+    /// Returns true when the 10-bit configuration has been chosen
+    #[inline]
+    pub fn deep_color(&self) -> bool {
+        self.deep_color
+    }
+
+    /// Returns the openGL objects that the renderer of this context shares with the others
+    #[inline]
+    pub fn share_group(&self) -> Rc<ShareGroup> {
+        self.share_group.clone()
     }
 
     #[inline]
@@ -75,6 +246,14 @@ impl EglContext {
         .with_context(|| "unable to make the context current")
     }
 
+    /// Set how many frames [EglContext::swap_buffers] waits for: 0 returns immediately, 1
+    /// waits for the vertical sync. The context must be current, and it has to be set again
+    /// after the surface is recreated
+    pub fn set_swap_interval(&self, interval: i32) -> Result<()> {
+        egl.swap_interval(self.display, interval)
+            .with_context(|| format!("unable to set the swap interval to {interval}"))
+    }
+
     // Swap the buffers of the surface
     #[inline]
     pub fn swap_buffers(&self) -> Result<()> {
@@ -82,27 +261,78 @@ impl EglContext {
             .with_context(|| "unable to post the surface content")
     }
 
+    /// Recreate the surface with or without the sRGB colorspace
+    pub fn update_srgb(
+        &mut self,
+        wl_surface: &WlSurface,
+        srgb: bool,
+        width: i32,
+        height: i32,
+    ) -> Result<()> {
+        let prev_srgb = std::mem::replace(&mut self.srgb, srgb);
+        let res = self.resize(wl_surface, width, height);
+        if res.is_err() {
+            // The previous surface has been kept
+            self.srgb = prev_srgb;
+        }
+        res
+    }
+
     /// Resize the surface
-    /// Resizing the surface means to destroy the previous one and then recreate it
+    /// Resizing the surface means to create a new one and then destroy the previous one.
+    /// If the new surface cannot be created, the previous one is kept intact
     pub fn resize(&mut self, wl_surface: &WlSurface, width: i32, height: i32) -> Result<()> {
-        egl.destroy_surface(self.display, self.surface)
-            .context("unable to destroy EGL surface")?;
         let wl_egl_surface = WlEglSurface::new(wl_surface.id(), width, height)
             .context("unable to create a new WlEglSurface")?;
 
-        let surface = unsafe {
-            egl.create_window_surface(
-                self.display,
-                self.config,
-                wl_egl_surface.ptr() as egl::NativeWindowType,
-                None,
-            )
-            .context("unable to create an EGL surface")?
-        };
+        let (surface, srgb) =
+            create_window_surface(self.display, self.config, &wl_egl_surface, self.srgb)?;
+        self.srgb = srgb;
 
-        self.surface = surface;
-        self.wl_egl_surface = wl_egl_surface;
+        let old_surface = std::mem::replace(&mut self.surface, surface);
+        let old_wl_egl_surface = std::mem::replace(&mut self.wl_egl_surface, wl_egl_surface);
+        // A current surface is only destroyed once it is not current anymore, switch to the
+        // new one so that the previous native window is not used after being dropped
+        let res = if egl.get_current_surface(egl::DRAW) == Some(old_surface) {
+            self.make_current()
+        } else {
+            Ok(())
+        };
+        // The EGL surface must be destroyed before its native window
+        let res = res.and_then(|_| {
+            egl.destroy_surface(self.display, old_surface)
+                .context("unable to destroy EGL surface")
+        });
+        match res {
+            Ok(()) => drop(old_wl_egl_surface),
+            Err(err) => {
+                // The new surface is already in use, the EGL surface might still use the
+                // previous native window so leak it instead
+                warn!("{err:?}");
+                std::mem::forget(old_wl_egl_surface);
+            }
+        }
 
         Ok(())
     }
 }
+
+impl Drop for EglContext {
+    fn drop(&mut self) {
+        // The context and its surface are only destroyed once they are not current anymore
+        if egl.get_current_context() == Some(self.context) {
+            if let Err(err) = egl.make_current(self.display, None, None, None) {
+                warn!("unable to release the EGL context: {err:?}");
+            }
+        }
+        // The EGL surface must be destroyed before its native window, dropped right after
+        if let Err(err) = egl.destroy_surface(self.display, self.surface) {
+            warn!("unable to destroy the EGL surface: {err:?}");
+        }
+        // The objects shared with the other contexts are kept until the last one is
+        // destroyed
+        if let Err(err) = egl.destroy_context(self.display, self.context) {
+            warn!("unable to destroy the EGL context: {err:?}");
+        }
+    }
+}
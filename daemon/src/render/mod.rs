@@ -1,7 +1,11 @@
+mod animation;
+mod blur;
 mod coordinates;
 mod egl_context;
 mod renderer;
 mod shader;
+mod share_group;
+mod texture_cache;
 mod transition;
 mod wallpaper;
 
@@ -12,10 +16,13 @@ use color_eyre::{
     Result,
 };
 use coordinates::{get_opengl_point_coordinates, Coordinates};
-use image::DynamicImage;
+use image::RgbaImage;
 
-pub use egl_context::EglContext;
+use crate::{image_loader::Rgba16Image, wallpaper_info::TextureFilter};
+
+pub use egl_context::{EglContext, ShareContexts};
 pub use renderer::Renderer;
+pub use share_group::ShareGroup;
 pub use transition::Transition;
 
 pub mod gl {
@@ -26,23 +33,36 @@ pub mod gl {
 }
 
 // Macro that check the error code of the last OpenGL call and returns a Result.
+// The error contains the location of the call, unless another one is passed.
 #[macro_export]
 macro_rules! gl_check {
-    ($gl:expr, $desc:expr) => {{
+    ($gl:expr, $desc:expr) => {
+        $crate::gl_check!($gl, $desc, file!(), line!())
+    };
+    ($gl:expr, $desc:expr, $file:expr, $line:expr) => {{
         let error = $gl.GetError();
         if error != gl::NO_ERROR {
             let error_string = $gl.GetString(error);
             ensure!(
                 !error_string.is_null(),
-                "OpenGL error when {}: {}",
+                "OpenGL error when {} at {}:{}: {}",
                 $desc,
+                $file,
+                $line,
                 error
             );
 
             let error_string = CStr::from_ptr(error_string as _)
                 .to_string_lossy()
                 .into_owned();
-            bail!("OpenGL error when {}: {} ({})", $desc, error, error_string);
+            bail!(
+                "OpenGL error when {} at {}:{}: {} ({})",
+                $desc,
+                $file,
+                $line,
+                error,
+                error_string
+            );
         }
     }};
 }
@@ -103,13 +123,30 @@ fn initialize_objects(gl: &gl::Gl) -> Result<(gl::types::GLuint, gl::types::GLui
         gl.EnableVertexAttribArray(TEX_ATTRIB as gl::types::GLuint);
         gl_check!(gl, "enabling the texture attribute for the vertex");
 
-        // Set the Coordinates of the two triangles. These don't change during
-        // the running of the program.
-        let vertex_data = get_opengl_point_coordinates(
-            Coordinates::default_vec_coordinates(),
-            Coordinates::default_texture_coordinates(),
-        );
+        // Set the Coordinates of the two triangles. These only change when the
+        // wallpaper gets flipped
+        set_vertex_data(gl, vbo, false, false)?;
+
+        Ok((vbo, eab))
+    }
+}
+
+/// Fill the vertex buffer with the coordinates of the two triangles covering the display,
+/// mirroring the texture coordinates when the wallpaper is flipped
+fn set_vertex_data(
+    gl: &gl::Gl,
+    vbo: gl::types::GLuint,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+) -> Result<()> {
+    let vertex_data = get_opengl_point_coordinates(
+        Coordinates::default_vec_coordinates(),
+        Coordinates::default_texture_coordinates().flip(flip_horizontal, flip_vertical),
+    );
 
+    unsafe {
+        gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+        gl_check!(gl, "binding the vbo buffer");
         // Update the vertex buffer
         gl.BufferSubData(
             gl::ARRAY_BUFFER,
@@ -118,39 +155,236 @@ fn initialize_objects(gl: &gl::Gl) -> Result<(gl::types::GLuint, gl::types::GLui
             vertex_data.as_ptr() as *const _,
         );
         gl_check!(gl, "buffering the data");
+    }
 
-        Ok((vbo, eab))
+    Ok(())
+}
+
+/// How the wallpapers are loaded into textures
+#[derive(Clone, Copy, Debug)]
+pub struct TextureOptions {
+    pub filter: TextureFilter,
+    /// the texture is decoded to linear colors when sampled
+    pub srgb: bool,
+    /// level of anisotropic filtering, None when the extension is not available
+    pub anisotropy: Option<f32>,
+    /// multiply the colors by their alpha before uploading the image
+    pub premultiply_alpha: bool,
+    /// generate the mipmap, used by the linear filter when the image is scaled down
+    pub mipmaps: bool,
+    /// the framebuffer has 10 bits per channel, the deep images are loaded with as many
+    pub deep_color: bool,
+}
+
+impl TextureOptions {
+    /// Returns true when the textures loaded with `self` and `other` have the same texels,
+    /// i.e. they only differ in how they are sampled
+    pub fn same_texels(&self, other: &Self) -> bool {
+        (
+            self.srgb,
+            self.premultiply_alpha,
+            self.mipmaps,
+            self.deep_color,
+        ) == (
+            other.srgb,
+            other.premultiply_alpha,
+            other.mipmaps,
+            other.deep_color,
+        )
     }
 }
 
-fn load_texture(gl: &gl::Gl, image: DynamicImage) -> Result<gl::types::GLuint> {
-    Ok(unsafe {
-        let mut texture = 0;
-        gl.GenTextures(1, &mut texture);
-        gl_check!(gl, "generating textures");
-        gl.ActiveTexture(gl::TEXTURE1);
-        gl_check!(gl, "activating textures");
-        gl.BindTexture(gl::TEXTURE_2D, texture);
-        gl_check!(gl, "binding textures");
-        gl.TexImage2D(
-            gl::TEXTURE_2D,
-            0,
-            gl::RGBA.try_into().unwrap(),
-            image.width().try_into().unwrap(),
-            image.height().try_into().unwrap(),
-            0,
-            gl::RGBA,
+/// The pixels of an image loaded in a texture
+#[derive(Clone, Copy)]
+pub enum Pixels<'a> {
+    Rgba8(&'a RgbaImage),
+    /// Opaque pixels with 16 bits per channel
+    Rgba16(&'a Rgba16Image),
+}
+
+/// Image of a single black pixel, shown before the first wallpaper and faded from
+pub fn black_image() -> RgbaImage {
+    RgbaImage::from_raw(1, 1, vec![0, 0, 0, 255]).unwrap()
+}
+
+/// Load `pixels` in a new texture
+fn load_texture(gl: &gl::Gl, pixels: Pixels, options: TextureOptions) -> Result<gl::types::GLuint> {
+    let image = match pixels {
+        Pixels::Rgba8(image) => image,
+        // RGB10_A2 has no sRGB format, the shader expects the texture to decode the colors
+        Pixels::Rgba16(image) if options.deep_color && !options.srgb => {
+            let texels: Vec<u32> = image
+                .pixels()
+                .map(|pixel| {
+                    let [red, green, blue, _] = pixel
+                        .0
+                        .map(|channel| (channel as u32 * 1023 + 32767) / 65535);
+                    // The image is opaque
+                    red | green << 10 | blue << 20 | 3 << 30
+                })
+                .collect();
+            return unsafe {
+                create_texture(
+                    gl,
+                    gl::RGB10_A2,
+                    image.dimensions(),
+                    gl::UNSIGNED_INT_2_10_10_10_REV,
+                    texels.as_ptr() as *const c_void,
+                    options,
+                )
+            };
+        }
+        Pixels::Rgba16(image) => {
+            let (width, height) = image.dimensions();
+            let channels = image
+                .as_raw()
+                .iter()
+                .map(|channel| ((*channel as u32 * 255 + 32767) / 65535) as u8)
+                .collect();
+            let image = RgbaImage::from_raw(width, height, channels).unwrap();
+            return load_texture(gl, Pixels::Rgba8(&image), options);
+        }
+    };
+    let premultiplied = if options.premultiply_alpha {
+        premultiply_alpha(image)
+    } else {
+        None
+    };
+    let image = premultiplied.as_ref().unwrap_or(image);
+
+    unsafe {
+        create_texture(
+            gl,
+            if options.srgb {
+                gl::SRGB8_ALPHA8_EXT
+            } else {
+                gl::RGBA
+            },
+            image.dimensions(),
             gl::UNSIGNED_BYTE,
-            image.as_bytes().as_ptr() as *const c_void,
-        );
-        gl_check!(gl, "defining the texture");
+            image.as_raw().as_ptr() as *const c_void,
+            options,
+        )
+    }
+}
+
+/// Create a texture from the RGBA `texels` of type `texel_type`
+unsafe fn create_texture(
+    gl: &gl::Gl,
+    internal_format: gl::types::GLenum,
+    (width, height): (u32, u32),
+    texel_type: gl::types::GLenum,
+    texels: *const c_void,
+    options: TextureOptions,
+) -> Result<gl::types::GLuint> {
+    let mut texture = 0;
+    gl.GenTextures(1, &mut texture);
+    gl_check!(gl, "generating textures");
+    gl.ActiveTexture(gl::TEXTURE1);
+    gl_check!(gl, "activating textures");
+    gl.BindTexture(gl::TEXTURE_2D, texture);
+    gl_check!(gl, "binding textures");
+    gl.TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        internal_format.try_into().unwrap(),
+        width.try_into().unwrap(),
+        height.try_into().unwrap(),
+        0,
+        gl::RGBA,
+        texel_type,
+        texels,
+    );
+    gl_check!(gl, "defining the texture");
+    // Also generated for nearest sampling, so that the filter can be changed without
+    // loading the image again
+    if options.mipmaps {
         gl.GenerateMipmap(gl::TEXTURE_2D);
         gl_check!(gl, "generating the mipmap");
-        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        gl_check!(gl, "defining the texture min filter");
-        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        gl_check!(gl, "defining the texture mag filter");
+    }
+
+    Ok(texture)
+}
 
-        texture
-    })
+/// Returns the maximum width and height of a texture supported by the GPU
+fn max_texture_size(gl: &gl::Gl) -> Result<u32> {
+    let mut max_size = 0;
+    unsafe {
+        gl.GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_size);
+        gl_check!(gl, "getting the maximum texture size");
+    }
+    // The minimum required by openGL ES 2.0
+    Ok(max_size.try_into().unwrap_or(0).max(64))
+}
+
+/// Multiply the colors of the image by their alpha, so that the transparent pixels do not
+/// bleed into their neighbours when sampled. Returns None for opaque images, which
+/// would be left untouched
+pub fn premultiply_alpha(image: &RgbaImage) -> Option<RgbaImage> {
+    if image.pixels().all(|pixel| pixel[3] == u8::MAX) {
+        return None;
+    }
+    let mut image = image.clone();
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+    Some(image)
+}
+
+/// Set the filter of `sampler`; the textures are sampled through the samplers of their
+/// renderer, as the same texture might be filtered differently on each display
+fn set_sampler_filter(
+    gl: &gl::Gl,
+    sampler: gl::types::GLuint,
+    options: TextureOptions,
+) -> Result<()> {
+    let filter = match options.filter {
+        TextureFilter::Linear => gl::LINEAR,
+        TextureFilter::Nearest => gl::NEAREST,
+    } as i32;
+    // Without the mipmap, a mipmap filter would make the texture incomplete and black
+    let min_filter = match options.filter {
+        TextureFilter::Linear if options.mipmaps => gl::LINEAR_MIPMAP_LINEAR as i32,
+        _ => filter,
+    };
+    unsafe {
+        gl.SamplerParameteri(sampler, gl::TEXTURE_MIN_FILTER, min_filter);
+        gl_check!(gl, "defining the sampler min filter");
+        gl.SamplerParameteri(sampler, gl::TEXTURE_MAG_FILTER, filter);
+        gl_check!(gl, "defining the sampler mag filter");
+        if let Some(anisotropy) = options.anisotropy {
+            // Keep the pixels sharp
+            let anisotropy = match options.filter {
+                TextureFilter::Linear => anisotropy,
+                TextureFilter::Nearest => 1.0,
+            };
+            gl.SamplerParameterf(sampler, gl::TEXTURE_MAX_ANISOTROPY_EXT, anisotropy);
+            gl_check!(gl, "defining the sampler anisotropy");
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the maximum level of anisotropic filtering, or None if it is not supported
+fn max_anisotropy(gl: &gl::Gl) -> Option<f32> {
+    unsafe {
+        let extensions = gl.GetString(gl::EXTENSIONS);
+        if extensions.is_null() {
+            return None;
+        }
+        let extensions = CStr::from_ptr(extensions as _).to_string_lossy();
+        if !extensions
+            .split_ascii_whitespace()
+            .any(|extension| extension == "GL_EXT_texture_filter_anisotropic")
+        {
+            return None;
+        }
+        let mut max_anisotropy = 0.0;
+        gl.GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max_anisotropy);
+        (gl.GetError() == gl::NO_ERROR).then_some(max_anisotropy)
+    }
 }
@@ -1,175 +1,600 @@
 use std::{cell::RefCell, ffi::CStr, ops::Deref, rc::Rc};
 
 use color_eyre::{
-    eyre::{bail, ensure, Context},
+    eyre::{bail, ensure, Context, ContextCompat},
     Result,
 };
 use egl::API as egl;
-use image::{DynamicImage, RgbaImage};
+use image::RgbaImage;
 use log::error;
 use smithay_client_toolkit::reexports::client::protocol::wl_output::Transform;
 
 use crate::{
     display_info::DisplayInfo,
     gl_check,
+    image_loader::{ImageData, ImageFrames, ImageIdentity},
     render::{
-        initialize_objects, load_texture,
+        initialize_objects, load_texture, max_anisotropy, max_texture_size, set_sampler_filter,
+        set_vertex_data,
         shader::{create_shader, FRAGMENT_SHADER_SOURCE, VERTEX_SHADER_SOURCE},
+        Pixels, TextureOptions,
     },
-    wallpaper_info::BackgroundMode,
+    wallpaper_info::{BackgroundMode, Easing, TextureFilter, WrapMode},
 };
 
-use super::{gl, wallpaper::Wallpaper, Transition};
+use super::{
+    animation::{Animation, KenBurns},
+    black_image,
+    blur::Blur,
+    coordinates::Coordinates,
+    gl,
+    texture_cache::TextureCache,
+    wallpaper::Wallpaper,
+    ShareGroup, Transition,
+};
 
 fn transparent_image() -> RgbaImage {
     RgbaImage::from_raw(1, 1, vec![0, 0, 0, 0]).unwrap()
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TransitionStatus {
     Started,
     Running { started: u32, progress: f32 },
     Ended,
 }
 
+impl TransitionStatus {
+    /// Advance the transition up to `time`. Without a time, i.e. when drawing outside of a
+    /// frame callback, the transition does not move and only starts with the next frame
+    fn advance(self, time: Option<u32>, transition_time: u32) -> Self {
+        let Some(time) = time else {
+            return self;
+        };
+        let started = match self {
+            TransitionStatus::Started => time,
+            TransitionStatus::Running {
+                started,
+                progress: _,
+            } => started,
+            TransitionStatus::Ended => return self,
+        };
+        let progress = if transition_time == 0 {
+            // The transition time might have been changed while the transition was running
+            1.0
+        } else {
+            ((time.saturating_sub(started)) as f32 / transition_time as f32).min(1.0)
+        };
+        if progress == 1.0 {
+            TransitionStatus::Ended
+        } else {
+            TransitionStatus::Running { started, progress }
+        }
+    }
+}
+
+/// Uniforms computed by [Renderer::set_mode]. They are uploaded before each draw, as the
+/// program is shared with the renderers of the other displays
+#[derive(Default)]
+struct Layout {
+    texture_scale: [f32; 2],
+    prev_texture_scale: [f32; 2],
+    texture_origin: [f32; 2],
+    prev_texture_origin: [f32; 2],
+    ratio: f32,
+    display_size: [f32; 2],
+    inset_scale: [f32; 2],
+    padding: f32,
+    corner_radius: f32,
+    /// wraps emulated by the shader for the previous and the current wallpaper
+    emulated_wraps: [i32; 2],
+}
+
 pub struct Renderer {
     gl: gl::Gl,
     pub program: gl::types::GLuint,
+    /// the programs and the textures shared with the renderers of the other displays
+    share_group: Rc<ShareGroup>,
+    /// samplers of the previous and the current wallpaper, in TEXTURE0 and TEXTURE1. The
+    /// textures might be shared, so they are filtered and wrapped by the samplers instead
+    samplers: [gl::types::GLuint; 2],
+    layout: Layout,
+    projection_matrix: [f32; 4],
     vbo: gl::types::GLuint,
     eab: gl::types::GLuint,
     // milliseconds time for the transition
     transition_time: u32,
+    easing: Easing,
+    // minimum milliseconds between two frames of the transition, 0 for no limit
+    frame_interval: u32,
+    // time of the last frame drawn during the transition
+    last_frame_time: Option<u32>,
     pub display_info: Rc<RefCell<DisplayInfo>>,
     prev_wallpaper: Option<Wallpaper>,
     current_wallpaper: Wallpaper,
+    /// identifies the image loaded in current_wallpaper, to skip reloading the same image
+    current_identity: Option<ImageIdentity>,
+    /// textures of the wallpapers shown recently
+    texture_cache: TextureCache,
+    /// frames of the current wallpaper, when it is animated
+    animation: Option<Animation>,
+    /// slow zoom and pan of the wallpaper in center mode
+    ken_burns: Option<KenBurns>,
+    mode: BackgroundMode,
+    offset: Option<[f32; 2]>,
+    /// mode and offset of the current wallpaper before they changed, it is shown in place
+    /// of the previous wallpaper while the transition animates the change
+    prev_layout: Option<(BackgroundMode, Option<[f32; 2]>)>,
+    /// size of each tile relative to the image, in tile mode
+    tile_scale: f32,
+    /// wrap chosen by the user for the modes that can use any of them
+    wrap_mode: Option<WrapMode>,
+    /// space left around the wallpaper and radius of its corners, in logical pixels
+    padding: i32,
+    corner_radius: i32,
     transparent_texture: gl::types::GLuint,
+    /// shown in place of the previous wallpaper when fading from black
+    black_texture: gl::types::GLuint,
+    /// fade every new wallpaper in from black, instead of the previous wallpaper
+    fade_from_black: bool,
+    /// color of the texture border, shown around the wallpaper in fit mode
+    fill_color: [f32; 4],
+    texture_options: TextureOptions,
+    /// maximum width and height of a texture, bigger images are scaled down when decoded
+    max_texture_size: u32,
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    /// multiplier of each color channel, applied after the other adjustments
+    tint: [f32; 3],
+    /// how much the edges are darkened, 0 disables the vignette
+    vignette_strength: f32,
+    /// distance from the center where the vignette starts, 1 being the corners
+    vignette_radius: f32,
+    /// alpha multiplier of the wallpaper, blending is only enabled below 1
+    opacity: f32,
+    /// standard deviation of the blur, in pixels
+    blur_sigma: f32,
+    /// framebuffers used to blur the wallpaper, only allocated when the blur is enabled
+    blur: Option<Blur>,
     /// contains the progress of the current animation
     transition_status: TransitionStatus,
+    /// true when what has been drawn on the screen is outdated
+    needs_redraw: bool,
 }
 
 impl Renderer {
     pub unsafe fn new(
-        image: DynamicImage,
+        share_group: Rc<ShareGroup>,
         display_info: Rc<RefCell<DisplayInfo>>,
         transition_time: u32,
         transition: Transition,
         transform: Transform,
+        fill_color: [f32; 4],
+        texture_filter: TextureFilter,
     ) -> Result<Self> {
         let gl = gl::Gl::load_with(|name| {
             egl.get_proc_address(name)
                 .expect("egl.get_proc_address to work") as *const std::ffi::c_void
         });
 
-        let program = create_program(&gl, transition)
-            .context("unable to create program during openGL ES initialization")?;
+        let program = match acquire_program(&gl, &share_group, transition) {
+            Ok(program) => program,
+            Err(err) => {
+                // i.e. a broken custom transition, do not leave the display without a wallpaper
+                error!("{err:?}");
+                acquire_program(&gl, &share_group, Transition::Fade {})
+                    .context("unable to create program during openGL ES initialization")?
+            }
+        };
+
+        let mut samplers = [0; 2];
+        gl.GenSamplers(2, samplers.as_mut_ptr());
+        gl_check!(gl, "generating the samplers");
+        for (texture_unit, sampler) in (0..).zip(samplers) {
+            gl.BindSampler(texture_unit, sampler);
+            gl_check!(gl, "binding the sampler");
+        }
 
         let (vbo, eab) = initialize_objects(&gl)?;
 
         let current_wallpaper = Wallpaper::new();
 
-        let transparent_texture = load_texture(&gl, transparent_image().into())?;
+        let max_texture_size = max_texture_size(&gl)?;
+        let plain_texture_options = TextureOptions {
+            filter: TextureFilter::Linear,
+            srgb: false,
+            anisotropy: None,
+            premultiply_alpha: false,
+            mipmaps: false,
+            deep_color: false,
+        };
+        let transparent_texture = load_texture(
+            &gl,
+            Pixels::Rgba8(&transparent_image()),
+            plain_texture_options,
+        )?;
+        let black_texture =
+            load_texture(&gl, Pixels::Rgba8(&black_image()), plain_texture_options)?;
 
         let mut renderer = Self {
             gl,
             program,
+            texture_cache: TextureCache::new(share_group.clone()),
+            share_group,
+            samplers,
+            layout: Layout::default(),
+            projection_matrix: projection_matrix(transform),
             vbo,
             eab,
             transition_time,
+            easing: Easing::default(),
+            frame_interval: 0,
+            last_frame_time: None,
             prev_wallpaper: None,
             current_wallpaper,
+            current_identity: None,
+            animation: None,
+            ken_burns: None,
+            mode: BackgroundMode::Stretch,
+            offset: None,
+            prev_layout: None,
+            tile_scale: 1.0,
+            wrap_mode: None,
+            padding: 0,
+            corner_radius: 0,
             display_info,
             transparent_texture,
+            black_texture,
+            fade_from_black: false,
+            fill_color,
+            texture_options: TextureOptions {
+                filter: texture_filter,
+                srgb: false,
+                anisotropy: None,
+                premultiply_alpha: false,
+                mipmaps: true,
+                deep_color: false,
+            },
+            max_texture_size,
+            brightness: 1.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            tint: [1.0, 1.0, 1.0],
+            vignette_strength: 0.0,
+            vignette_radius: 0.5,
+            opacity: 1.0,
+            blur_sigma: 0.0,
+            blur: None,
             transition_status: TransitionStatus::Ended,
+            needs_redraw: true,
         };
 
-        renderer.load_wallpaper(image, BackgroundMode::Stretch, None)?;
-        renderer.set_projection_matrix(transform)?;
+        renderer.set_texture_options()?;
+        renderer.load_wallpaper(
+            ImageData::new(ImageFrames::Static(black_image())),
+            None,
+            BackgroundMode::Stretch,
+            None,
+        )?;
 
         Ok(renderer)
     }
 
+    /// Check the error of the last OpenGL call, reporting the location of the caller
     #[inline]
+    #[track_caller]
     pub fn check_error(&self, msg: &str) -> Result<()> {
+        let location = std::panic::Location::caller();
         unsafe {
-            gl_check!(self.gl, msg);
+            gl_check!(self.gl, msg, location.file(), location.line());
         }
         Ok(())
     }
 
     pub unsafe fn draw(&mut self) -> Result<()> {
+        if let Some(blur) = &self.blur {
+            // Draw the wallpaper in the framebuffer, it will be blurred later
+            blur.bind_framebuffer(&self.gl)?;
+        }
         self.gl.Clear(gl::COLOR_BUFFER_BIT);
         self.check_error("clearing the screen")?;
 
-        let loc = self
-            .gl
-            .GetUniformLocation(self.program, b"progress\0".as_ptr() as *const _);
-        self.check_error("getting the uniform location")?;
-        self.gl.Uniform1f(
-            loc,
-            match self.transition_status {
-                TransitionStatus::Started => 0.0,
-                TransitionStatus::Running {
-                    started: _,
-                    progress,
-                } => progress,
-                TransitionStatus::Ended => 1.0,
-            },
-        );
-        self.check_error("calling Uniform1i")?;
+        // The other displays sharing the program have changed its uniforms
+        self.gl.UseProgram(self.program);
+        self.check_error("calling UseProgram")?;
+        self.upload_uniforms()?;
 
         self.gl
             .DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null());
         self.check_error("drawing the triangles")?;
 
+        if let Some(blur) = &self.blur {
+            blur.draw(&self.gl, self.blur_sigma)?;
+            self.gl.UseProgram(self.program);
+            self.check_error("calling UseProgram")?;
+        }
+
+        // Once the transition has ended, this is the final frame and there is nothing left
+        // to draw until something changes
+        self.needs_redraw = self.transition_running();
+
         Ok(())
     }
 
-    /// Update the transition status with the current time
+    /// Update the transition status with the current time, returns true while the transition
+    /// is running
     #[inline]
-    pub fn update_transition_status(&mut self, time: u32) -> bool {
-        let started = match self.transition_status {
-            TransitionStatus::Started => time,
-            TransitionStatus::Running {
-                started,
-                progress: _,
-            } => started,
-            TransitionStatus::Ended => unreachable!(),
-        };
-        let progress =
-            ((time.saturating_sub(started)) as f32 / self.transition_time as f32).min(1.0);
-        if progress == 1.0 {
-            self.transition_status = TransitionStatus::Ended;
-            false
-        } else {
-            self.transition_status = TransitionStatus::Running { started, progress };
-            true
+    pub fn update_transition_status(&mut self, time: Option<u32>) -> bool {
+        self.transition_status = self.transition_status.advance(time, self.transition_time);
+        self.transition_status != TransitionStatus::Ended
+    }
+
+    /// Read the content of the framebuffer, i.e. what has just been drawn. It must be called
+    /// after [Renderer::draw] and before swapping the buffers
+    pub fn capture(&self) -> Result<RgbaImage> {
+        let info = (*self.display_info).borrow();
+        let width = info.adjusted_width();
+        let height = info.adjusted_height();
+        drop(info);
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            self.gl.ReadPixels(
+                0,
+                0,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
         }
+        self.check_error("reading the pixels")?;
+
+        // openGL starts from the bottom row, images from the top one
+        let row_len = width as usize * 4;
+        let pixels = pixels
+            .chunks_exact(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        RgbaImage::from_raw(width as u32, height as u32, pixels)
+            .context("the pixels read do not match the size of the display")
     }
 
+    /// Load a new wallpaper and make it the current one. Returns false when the image
+    /// is the same as the current one: only its mode is updated and there is nothing
+    /// to transition to
     pub fn load_wallpaper(
         &mut self,
-        image: DynamicImage,
+        image: ImageData,
+        identity: Option<ImageIdentity>,
         mode: BackgroundMode,
-        offset: Option<f32>,
-    ) -> Result<()> {
-        self.prev_wallpaper = Some(std::mem::take(&mut self.current_wallpaper));
-        self.current_wallpaper.load_image(&self.gl, image)?;
+        offset: Option<[f32; 2]>,
+    ) -> Result<bool> {
+        if identity.is_some() && identity == self.current_identity {
+            if self.mode != mode || self.offset != offset {
+                self.set_mode(mode, offset)?;
+            }
+            return Ok(false);
+        }
+
+        self.snap_transition();
+        self.current_identity = identity.clone();
+        let current_wallpaper = std::mem::take(&mut self.current_wallpaper);
+        self.replace_prev_wallpaper(current_wallpaper);
+        let size = (image.width, image.height);
+        // Another display has uploaded the same image while this one was decoding it
+        let shared = identity.as_ref().and_then(|identity| {
+            self.share_group
+                .acquire_texture(identity, self.texture_options)
+        });
+        self.animation = if let Some(wallpaper) = shared {
+            self.current_wallpaper = wallpaper;
+            unsafe {
+                // Like load_texture, the current wallpaper goes into TEXTURE1
+                self.gl.ActiveTexture(gl::TEXTURE1);
+                self.check_error("activating gl::TEXTURE1")?;
+                self.current_wallpaper.bind(&self.gl)?;
+            }
+            None
+        } else {
+            match image.frames {
+                ImageFrames::Static(frame) => {
+                    self.current_wallpaper.load_image(
+                        &self.gl,
+                        Pixels::Rgba8(&frame),
+                        size,
+                        self.texture_options,
+                    )?;
+                    self.current_wallpaper.identity = identity;
+                    self.share_group
+                        .share_texture(&self.current_wallpaper, self.texture_options);
+                    None
+                }
+                ImageFrames::Deep(frame) => {
+                    self.current_wallpaper.load_image(
+                        &self.gl,
+                        Pixels::Rgba16(&frame),
+                        size,
+                        self.texture_options,
+                    )?;
+                    self.current_wallpaper.identity = identity;
+                    self.share_group
+                        .share_texture(&self.current_wallpaper, self.texture_options);
+                    None
+                }
+                ImageFrames::Animated(frames) => {
+                    let animation = Animation::new(frames);
+                    self.current_wallpaper.load_image(
+                        &self.gl,
+                        Pixels::Rgba8(animation.current_frame()),
+                        size,
+                        self.texture_options,
+                    )?;
+                    Some(animation)
+                }
+            }
+        };
 
         self.bind_wallpapers(mode, offset)?;
 
+        Ok(true)
+    }
+
+    #[inline]
+    pub fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
+    }
+
+    /// Returns true when the texture of the image is in the cache, or has been loaded by
+    /// another display of the share group
+    #[inline]
+    pub fn is_cached(&self, identity: &ImageIdentity) -> bool {
+        self.texture_cache.contains(identity)
+            || self
+                .share_group
+                .contains_texture(identity, self.texture_options)
+    }
+
+    /// Make a wallpaper from the texture cache or from the share group the current one,
+    /// without uploading its image again
+    pub fn load_cached_wallpaper(
+        &mut self,
+        identity: &ImageIdentity,
+        mode: BackgroundMode,
+        offset: Option<[f32; 2]>,
+    ) -> Result<()> {
+        let wallpaper = self
+            .texture_cache
+            .take(identity)
+            .or_else(|| {
+                self.share_group
+                    .acquire_texture(identity, self.texture_options)
+            })
+            .context("the wallpaper is not in the texture cache")?;
+        self.snap_transition();
+        self.current_identity = Some(identity.clone());
+        let current_wallpaper = std::mem::replace(&mut self.current_wallpaper, wallpaper);
+        self.replace_prev_wallpaper(current_wallpaper);
+        self.animation = None;
+
+        unsafe {
+            // Like load_texture, the current wallpaper goes into TEXTURE1
+            self.gl.ActiveTexture(gl::TEXTURE1);
+            self.check_error("activating gl::TEXTURE1")?;
+            self.current_wallpaper.bind(&self.gl)?;
+        }
+        self.bind_wallpapers(mode, offset)
+    }
+
+    /// Complete the running transition right away, so that loading a new wallpaper never
+    /// blends three of them. The surface usually waits for the transition to end instead
+    fn snap_transition(&mut self) {
+        if self.transition_running() {
+            self.force_transition_end();
+            self.transition_finished();
+        }
+    }
+
+    /// The previous wallpaper is usually gone after the transition, but a reload might
+    /// replace it before that
+    fn replace_prev_wallpaper(&mut self, wallpaper: Wallpaper) {
+        self.prev_layout = None;
+        if let Some(prev_wallpaper) = self.prev_wallpaper.replace(wallpaper) {
+            self.texture_cache.insert(&self.gl, prev_wallpaper);
+        }
+    }
+
+    /// Show the next frame of an animated wallpaper when its time has come
+    pub fn update_animation(&mut self, time: u32) -> Result<()> {
+        if let Some(ken_burns) = &mut self.ken_burns {
+            if self.mode == BackgroundMode::Center && ken_burns.advance(time, self.frame_interval) {
+                self.set_mode(self.mode, self.offset)?;
+            }
+        }
+        let Some(animation) = &mut self.animation else {
+            return Ok(());
+        };
+        if animation.advance(time) {
+            // load_image replaces the texture of the previous frame
+            let size = (
+                self.current_wallpaper.image_width,
+                self.current_wallpaper.image_height,
+            );
+            self.current_wallpaper.load_image(
+                &self.gl,
+                Pixels::Rgba8(animation.current_frame()),
+                size,
+                self.texture_options,
+            )?;
+            if self.prev_layout.is_some() {
+                // The mode change shows the current wallpaper in both texture units, the
+                // previous frame has just been deleted
+                unsafe {
+                    self.gl.ActiveTexture(gl::TEXTURE0);
+                    self.check_error("activating gl::TEXTURE0")?;
+                    self.current_wallpaper.bind(&self.gl)?;
+                    self.gl.ActiveTexture(gl::TEXTURE1);
+                    self.check_error("activating gl::TEXTURE1")?;
+                }
+            }
+            // The new texture needs the same parameters as the previous frame
+            self.set_mode(self.mode, self.offset)?;
+        }
+
         Ok(())
     }
 
-    fn bind_wallpapers(&mut self, mode: BackgroundMode, offset: Option<f32>) -> Result<()> {
+    /// Returns the time when an animated wallpaper has to be drawn again, or None if it has
+    /// to be drawn at the next frame
+    pub fn next_animation_time(&self) -> Option<u32> {
+        let mut next_time = None;
+        if let Some(ken_burns) = &self.ken_burns {
+            if self.mode == BackgroundMode::Center {
+                next_time = Some(ken_burns.next_update_time(self.frame_interval)?);
+            }
+        }
+        if let Some(animation) = &self.animation {
+            let frame_time = animation.next_frame_time()?;
+            next_time = Some(next_time.map_or(frame_time, |time: u32| time.min(frame_time)));
+        }
+        next_time
+    }
+
+    /// Returns true when the wallpaper changes over time and needs to be drawn continuously
+    #[inline]
+    pub fn is_animated(&self) -> bool {
+        self.animation.is_some()
+            || (self.ken_burns.is_some() && self.mode == BackgroundMode::Center)
+    }
+
+    /// Zoom slowly into the wallpaper up to `zoom` and back again in `cycle_time`
+    /// milliseconds; it only applies to center mode, the only mode cropping the image.
+    /// A zoom of 1.0 disables the effect
+    pub fn update_ken_burns(&mut self, zoom: f32, cycle_time: u32) -> Result<()> {
+        self.ken_burns = (zoom > 1.0).then(|| KenBurns::new(zoom, cycle_time));
+        self.set_mode(self.mode, self.offset)
+    }
+
+    fn bind_wallpapers(&mut self, mode: BackgroundMode, offset: Option<[f32; 2]>) -> Result<()> {
         self.set_mode(mode, offset)?;
 
         unsafe {
             self.gl.ActiveTexture(gl::TEXTURE0);
             self.check_error("activating gl::TEXTURE0")?;
-            self.prev_wallpaper
-                .as_ref()
-                .expect("previous wallpaper to be set")
-                .bind(&self.gl)?;
+            if self.fade_from_black {
+                // The previous wallpaper is kept until the end of the transition, so that
+                // it can still be cached
+                self.gl.BindTexture(gl::TEXTURE_2D, self.black_texture);
+                self.check_error("binding the black texture")?;
+            } else {
+                self.prev_wallpaper
+                    .as_ref()
+                    .expect("previous wallpaper to be set")
+                    .bind(&self.gl)?;
+            }
 
             // current_wallpaper is already binded to TEXTURE1, as load_texture loads the image
             // there
@@ -178,152 +603,423 @@ impl Renderer {
         Ok(())
     }
 
-    pub fn set_mode(&mut self, mode: BackgroundMode, offset: Option<f32>) -> Result<()> {
+    pub fn set_mode(&mut self, mode: BackgroundMode, offset: Option<[f32; 2]>) -> Result<()> {
+        self.needs_redraw = true;
+        self.mode = mode;
+        self.offset = offset;
         let display_info = (*self.display_info).borrow();
-        let display_width = display_info.scaled_width() as f32;
-        let display_height = display_info.scaled_height() as f32;
-        let display_ratio = display_width / display_height;
-        let gen_texture_scale = |image_width: f32, image_height: f32| {
-            let image_ratio: f32 = image_width / image_height;
-            Box::new(match mode {
-                BackgroundMode::Stretch => [1.0, 1.0],
-                BackgroundMode::Center => [
-                    (display_ratio / image_ratio).min(1.0),
-                    (image_ratio / display_ratio).min(1.0),
-                ],
-                BackgroundMode::Fit | BackgroundMode::FitBorderColor => {
-                    // Portrait mode
-                    // In this case we calculate the width relative to the height of the
-                    // screen with the ratio of the image
-                    let width = display_height * image_ratio;
-                    // Same thing as above, just with the width
-                    let height = display_width / image_ratio;
-                    // Then we calculate the proportions
-                    [
-                        (display_width / width).max(1.0),
-                        (display_height / height).max(1.0),
-                    ]
-                }
-                BackgroundMode::Tile => {
-                    let width_proportion = display_width / image_width * display_ratio;
-                    let height_proportion = display_height / image_height * display_ratio;
-                    if display_ratio > image_ratio {
-                        // Portrait mode
-                        if height_proportion.max(1.0) == 1.0 {
-                            // Same as Fit
-                            let width = display_height * image_ratio;
-                            [display_width / width, 1.0]
-                        } else {
-                            [width_proportion, height_proportion]
-                        }
-                    } else {
-                        // Landscape mode
-                        if width_proportion.max(1.0) == 1.0 {
-                            // Same as Fit
-                            let height = display_width / image_ratio;
-                            [1.0, display_height / height]
-                        } else {
-                            [width_proportion, height_proportion]
-                        }
-                    }
-                }
-            })
-        };
-        let texture_scale = gen_texture_scale(
-            self.current_wallpaper.image_width as f32,
-            self.current_wallpaper.image_height as f32,
+        let display_width = display_info.adjusted_width();
+        let display_height = display_info.adjusted_height();
+        let transform = display_info.transform;
+        let display_ratio =
+            display_info.scaled_width() as f32 / display_info.scaled_height() as f32;
+        // The wallpaper is scaled to the area inside the padding
+        // A huge padding only leaves a pixel for the wallpaper, do not overflow
+        let scaled_padding = self.padding.saturating_mul(display_info.scale);
+        let padding = scaled_padding as f32;
+        let corner_radius = self.corner_radius.saturating_mul(display_info.scale) as f32;
+        let (inset_width, inset_height) = inset_size(display_width, display_height, scaled_padding);
+        drop(display_info);
+        let inset_scale = calculate_inset_scale(
+            display_width,
+            display_height,
+            inset_width,
+            inset_height,
+            transform,
         );
-        let (prev_image_width, prev_image_height) = if let Some(prev_wp) = &self.prev_wallpaper {
-            (prev_wp.image_width as f32, prev_wp.image_height as f32)
+        let current = calculate_texture_coordinates(
+            mode,
+            offset,
+            (inset_width, inset_height),
+            transform,
+            (
+                self.current_wallpaper.image_width,
+                self.current_wallpaper.image_height,
+            ),
+            self.tile_scale,
+            self.ken_burns.as_ref(),
+        );
+        let (prev_mode, prev_offset) = self.prev_layout.unwrap_or((mode, offset));
+        let prev = calculate_texture_coordinates(
+            prev_mode,
+            prev_offset,
+            (inset_width, inset_height),
+            transform,
+            prev_image_size(
+                &self.current_wallpaper,
+                self.prev_wallpaper.as_ref(),
+                self.prev_layout.is_some(),
+            ),
+            self.tile_scale,
+            // Only the current wallpaper is zoomed
+            None,
+        );
+        // The corners cannot be rounder than the inset area
+        let corner_radius = corner_radius.min(inset_width.min(inset_height) as f32 / 2.0);
+
+        // Both texture units share the same texture while the mode change is animated,
+        // the shader wraps the coordinates of each of them when the wraps differ
+        let prev_wrap = texture_wrap(prev_mode, self.wrap_mode);
+        let current_wrap = texture_wrap(mode, self.wrap_mode);
+        let (texture_wrap, emulated_wraps) = if prev_wrap == current_wrap {
+            (current_wrap as i32, [0, 0])
         } else {
-            (1.0, 1.0)
+            (
+                gl::CLAMP_TO_EDGE as i32,
+                [emulated_wrap(prev_wrap), emulated_wrap(current_wrap)],
+            )
         };
 
-        let prev_texture_scale = gen_texture_scale(prev_image_width, prev_image_height);
+        self.layout = Layout {
+            texture_scale: current.size(),
+            prev_texture_scale: prev.size(),
+            texture_origin: current.origin(),
+            prev_texture_origin: prev.origin(),
+            ratio: display_ratio,
+            display_size: [display_width as f32, display_height as f32],
+            inset_scale,
+            padding,
+            corner_radius,
+            emulated_wraps,
+        };
 
         unsafe {
-            let loc = self
-                .gl
-                .GetUniformLocation(self.program, b"textureScale\0".as_ptr() as *const _);
-            self.check_error("getting the uniform location")?;
-            ensure!(loc > 0, "textureScale not found");
-            self.gl
-                .Uniform2fv(loc, 1, texture_scale.as_ptr() as *const _);
-            self.check_error("calling Uniform2fv on textureScale")?;
-
-            let loc = self
-                .gl
-                .GetUniformLocation(self.program, b"prevTextureScale\0".as_ptr() as *const _);
-            self.check_error("getting the uniform location")?;
-            ensure!(loc > 0, "prevTextureScale not found");
-            self.gl
-                .Uniform2fv(loc, 1, prev_texture_scale.as_ptr() as *const _);
-            self.check_error("calling Uniform2fv on prevTextureScale")?;
-
-            let loc = self
-                .gl
-                .GetUniformLocation(self.program, b"ratio\0".as_ptr() as *const _);
-            self.check_error("getting the uniform location")?;
-            self.gl.Uniform1f(loc, display_ratio);
-            self.check_error("calling Uniform1f")?;
+            for sampler in self.samplers {
+                self.gl
+                    .SamplerParameteri(sampler, gl::TEXTURE_WRAP_S, texture_wrap);
+                self.check_error("defining the sampler wrap_s")?;
+                self.gl
+                    .SamplerParameteri(sampler, gl::TEXTURE_WRAP_T, texture_wrap);
+                self.check_error("defining the sampler wrap_t")?;
+            }
+        }
 
-            let offset = match (offset, mode) {
-                (
-                    None,
-                    BackgroundMode::Stretch
-                    | BackgroundMode::Center
-                    | BackgroundMode::Fit
-                    | BackgroundMode::FitBorderColor,
-                ) => 0.5,
-                (None, BackgroundMode::Tile) => 0.0,
-                (Some(offset), _) => offset,
-            };
-
-            let loc = self
-                .gl
-                .GetUniformLocation(self.program, b"texture_offset\0".as_ptr() as *const _);
-            self.check_error("getting the uniform location")?;
-            self.gl.Uniform1f(loc, offset);
-            self.check_error("calling Uniform1f")?;
+        self.set_border_color()
+    }
 
-            let texture_wrap = match mode {
-                BackgroundMode::Stretch | BackgroundMode::Center | BackgroundMode::Fit => {
-                    gl::CLAMP_TO_BORDER_EXT
-                }
-                BackgroundMode::Tile => gl::REPEAT,
-                BackgroundMode::FitBorderColor => gl::CLAMP_TO_EDGE,
-            } as i32;
+    /// Change the mode of the current wallpaper with a transition between the two layouts,
+    /// by showing the current wallpaper in place of the previous one. Returns true when the
+    /// transition has to be started, false when the change has been applied right away
+    pub fn animate_mode_change(
+        &mut self,
+        mode: BackgroundMode,
+        offset: Option<[f32; 2]>,
+    ) -> Result<bool> {
+        // Do not interrupt the transition to a new wallpaper
+        if self.transition_running() || (self.mode, self.offset) == (mode, offset) {
+            self.set_mode(mode, offset)?;
+            return Ok(false);
+        }
 
+        // The previous wallpaper is replaced by the current one
+        if let Some(prev_wallpaper) = self.prev_wallpaper.take() {
+            self.texture_cache.insert(&self.gl, prev_wallpaper);
+        }
+        self.prev_layout = Some((self.mode, self.offset));
+        unsafe {
             self.gl.ActiveTexture(gl::TEXTURE0);
             self.check_error("activating gl::TEXTURE0")?;
-            self.gl
-                .TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, texture_wrap);
-            self.check_error("defining the texture wrap_s")?;
-            self.gl
-                .TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, texture_wrap);
-            self.check_error("defining the texture wrap_t")?;
-
+            self.current_wallpaper.bind(&self.gl)?;
             self.gl.ActiveTexture(gl::TEXTURE1);
             self.check_error("activating gl::TEXTURE1")?;
-            self.gl
-                .TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, texture_wrap);
-            self.check_error("defining the texture wrap_s")?;
-            self.gl
-                .TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, texture_wrap);
-            self.check_error("defining the texture wrap_t")?;
+        }
+        self.set_mode(mode, offset)?;
+
+        Ok(true)
+    }
+
+    /// Set the border color of both the previous and the current wallpaper, so that the
+    /// empty space in fit mode has the same color during the whole transition
+    fn set_border_color(&self) -> Result<()> {
+        let fill_color = self.border_color();
+        unsafe {
+            for sampler in self.samplers {
+                self.gl.SamplerParameterfv(
+                    sampler,
+                    gl::TEXTURE_BORDER_COLOR_EXT,
+                    fill_color.as_ptr(),
+                );
+                self.check_error("defining the sampler border color")?;
+            }
         }
 
         Ok(())
     }
 
+    /// Returns the fill color as sampled from the border of the textures
+    fn border_color(&self) -> [f32; 4] {
+        // The border color is not decoded like the texels of an sRGB texture
+        let mut fill_color = if self.texture_options.srgb {
+            let [r, g, b, a] = self.fill_color;
+            [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+        } else {
+            self.fill_color
+        };
+        // Blend the border like the texels of the wallpaper
+        if self.texture_options.premultiply_alpha {
+            let alpha = fill_color[3];
+            fill_color[..3].iter_mut().for_each(|c| *c *= alpha);
+        }
+        fill_color
+    }
+
+    /// Draw the wallpaper inside the display, leaving `padding` pixels on each side and
+    /// rounding its corners
+    pub fn update_padding(&mut self, padding: u32, corner_radius: u32) -> Result<()> {
+        self.padding = padding.try_into().context("padding is too big")?;
+        self.corner_radius = corner_radius
+            .try_into()
+            .context("corner radius is too big")?;
+        self.set_mode(self.mode, self.offset)
+    }
+
+    /// Switch the textures to sRGB. The current wallpaper needs to be loaded again to
+    /// take effect
+    pub fn update_srgb(&mut self, srgb: bool) -> Result<()> {
+        self.texture_options.srgb = srgb;
+        // The textures loaded so far have the wrong format
+        self.forget_textures(true);
+        self.needs_redraw = true;
+        self.set_border_color()?;
+        self.reset_blur()
+    }
+
+    /// Load the deep images with 10 bits per channel, as the framebuffer has as many
+    pub fn update_deep_color(&mut self, deep_color: bool) -> Result<()> {
+        self.texture_options.deep_color = deep_color;
+        self.forget_textures(true);
+        self.needs_redraw = true;
+        self.reset_blur()
+    }
+
+    /// Premultiply the alpha of the textures. The current wallpaper needs to be loaded
+    /// again to take effect
+    pub fn update_premultiply_alpha(&mut self, premultiply_alpha: bool) -> Result<()> {
+        self.texture_options.premultiply_alpha = premultiply_alpha;
+        self.forget_textures(true);
+        self.needs_redraw = true;
+        self.set_border_color()
+    }
+
+    /// Generate the mipmap of the textures. When enabling it, the current wallpaper needs
+    /// to be loaded again to take effect
+    pub fn update_mipmaps(&mut self, mipmaps: bool) -> Result<()> {
+        self.texture_options.mipmaps = mipmaps;
+        self.forget_textures(true);
+        self.needs_redraw = true;
+        if mipmaps {
+            // The textures loaded so far do not have a mipmap to sample from
+            Ok(())
+        } else {
+            self.set_texture_options()
+        }
+    }
+
+    pub fn update_texture_filter(&mut self, texture_filter: TextureFilter) -> Result<()> {
+        self.texture_options.filter = texture_filter;
+        self.set_texture_options()
+    }
+
+    /// Set the level of anisotropic filtering, ignored when the openGL implementation
+    /// does not support it
+    pub fn update_anisotropy(&mut self, anisotropy: f32) -> Result<()> {
+        self.texture_options.anisotropy =
+            max_anisotropy(&self.gl).map(|max_anisotropy| anisotropy.min(max_anisotropy));
+        self.set_texture_options()
+    }
+
+    /// Delete the cached textures after their options have changed, and do not cache the
+    /// previous wallpaper either. With `reload_current` the current wallpaper is not cached
+    /// and is loaded again, even if the image is the same
+    fn forget_textures(&mut self, reload_current: bool) {
+        self.texture_cache.clear(&self.gl);
+        if let Some(prev_wallpaper) = &mut self.prev_wallpaper {
+            prev_wallpaper.identity = None;
+        }
+        if reload_current {
+            self.current_identity = None;
+            self.current_wallpaper.identity = None;
+        }
+    }
+
+    fn set_texture_options(&mut self) -> Result<()> {
+        self.needs_redraw = true;
+        // Both the previous and the current wallpaper are filtered by their sampler
+        for sampler in self.samplers {
+            set_sampler_filter(&self.gl, sampler, self.texture_options)?;
+        }
+
+        Ok(())
+    }
+
+    /// Mirror the wallpaper; both the previous and the current wallpaper are flipped, so that
+    /// the transition is consistent
+    pub fn update_flip(&mut self, flip_horizontal: bool, flip_vertical: bool) -> Result<()> {
+        self.needs_redraw = true;
+        set_vertex_data(&self.gl, self.vbo, flip_horizontal, flip_vertical)
+    }
+
+    pub fn update_tile_scale(&mut self, tile_scale: f32) -> Result<()> {
+        self.tile_scale = tile_scale;
+        self.set_mode(self.mode, self.offset)
+    }
+
+    /// Choose how stretch, center and tile modes sample the wallpaper outside of its image,
+    /// None for the default of each mode
+    pub fn update_wrap_mode(&mut self, wrap_mode: Option<WrapMode>) -> Result<()> {
+        self.wrap_mode = wrap_mode;
+        self.set_mode(self.mode, self.offset)
+    }
+
     #[inline]
-    pub fn start_transition(&mut self, transition_time: u32) {
-        match self.transition_status {
-            TransitionStatus::Started | TransitionStatus::Running { .. } => unreachable!(),
-            TransitionStatus::Ended => self.transition_status = TransitionStatus::Started,
+    pub fn set_easing(&mut self, easing: Easing) {
+        self.easing = easing;
+    }
+
+    #[inline]
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness;
+        self.needs_redraw = true;
+    }
+
+    #[inline]
+    pub fn set_contrast(&mut self, contrast: f32) {
+        self.contrast = contrast;
+        self.needs_redraw = true;
+    }
+
+    #[inline]
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma;
+        self.needs_redraw = true;
+    }
+
+    /// Multiply each color channel of the wallpaper, `[1.0, 1.0, 1.0]` leaves it untouched
+    #[inline]
+    pub fn set_tint(&mut self, tint: [f32; 3]) {
+        self.tint = tint;
+        self.needs_redraw = true;
+    }
+
+    /// Draw the wallpaper semi-transparent, 1.0 leaves it opaque. The compositor only shows
+    /// what is below the wallpaper when the surface has no opaque region
+    #[inline]
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+        self.needs_redraw = true;
+    }
+
+    #[inline]
+    pub fn set_vignette(&mut self, strength: f32, radius: f32) {
+        self.vignette_strength = strength;
+        self.vignette_radius = radius;
+        self.needs_redraw = true;
+    }
+
+    /// Blur the wallpaper with a gaussian of standard deviation `sigma`, 0 disables the blur
+    pub fn set_blur(&mut self, sigma: f32) -> Result<()> {
+        self.blur_sigma = sigma;
+        self.needs_redraw = true;
+        self.update_blur()
+    }
+
+    /// Allocate the framebuffers of the blur once the size of the display is known, or free
+    /// them when the blur has been disabled
+    fn update_blur(&mut self) -> Result<()> {
+        let info = (*self.display_info).borrow();
+        let (width, height) = (info.adjusted_width(), info.adjusted_height());
+        drop(info);
+        match (&self.blur, self.blur_sigma > 0.0) {
+            // The display is not configured yet, resize will try again
+            (None, true) if width <= 0 || height <= 0 => {}
+            (None, true) => {
+                // Linear colors and 10-bit framebuffers both band with 8 bits per channel
+                let half_float = self.texture_options.srgb || self.texture_options.deep_color;
+                let blur = Blur::new(&self.gl, width, height, half_float);
+                // Creating the blur program switched the current one, and a failure might
+                // have left its texture unit active
+                unsafe {
+                    self.gl.UseProgram(self.program);
+                    self.check_error("calling UseProgram")?;
+                    self.gl.ActiveTexture(gl::TEXTURE1);
+                    self.check_error("activating gl::TEXTURE1")?;
+                }
+                self.blur = Some(blur?);
+            }
+            (Some(blur), false) => {
+                blur.delete(&self.gl);
+                self.blur = None;
+            }
+            (None, false) | (Some(_), true) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Allocate the framebuffers of the blur again, with the format of the current options
+    fn reset_blur(&mut self) -> Result<()> {
+        if let Some(blur) = self.blur.take() {
+            blur.delete(&self.gl);
         }
+        self.update_blur()
+    }
+
+    #[inline]
+    pub fn update_fill_color(&mut self, fill_color: [f32; 4]) -> Result<()> {
+        self.fill_color = fill_color;
+        self.needs_redraw = true;
+        self.set_border_color()
+    }
+
+    #[inline]
+    pub fn start_transition(&mut self, transition_time: u32) {
         // Needed to skip the initial transition depending on the configuration
         self.transition_time = transition_time;
+        if transition_time == 0 {
+            // There is nothing to animate, show the new wallpaper right away
+            self.transition_finished();
+            return;
+        }
+        // A running transition has already been completed by load_wallpaper, start over
+        self.transition_status = TransitionStatus::Started;
+        self.last_frame_time = None;
+        self.needs_redraw = true;
+    }
+
+    /// Limit the frames drawn during the transition, None for no limit
+    #[inline]
+    pub fn update_max_fps(&mut self, max_fps: Option<u32>) {
+        self.frame_interval = max_fps.map_or(0, |max_fps| 1000 / max_fps);
+    }
+
+    /// Returns the time when the next frame of the transition should be drawn, or None
+    /// if it can be drawn right away. The last frame is always drawn when the transition
+    /// ends, so that it shows the wallpaper with a progress of 1.0
+    pub fn next_frame_time(&self) -> Option<u32> {
+        let TransitionStatus::Running { started, .. } = self.transition_status else {
+            return None;
+        };
+        if self.frame_interval == 0 {
+            return None;
+        }
+        let last_frame_time = self.last_frame_time?;
+        Some(
+            last_frame_time
+                .saturating_add(self.frame_interval)
+                .min(started.saturating_add(self.transition_time)),
+        )
+    }
+
+    /// Check if the frame at `time` has to be skipped to respect the frame limit; when it
+    /// doesn't, it is considered drawn
+    pub fn skip_frame(&mut self, time: u32) -> bool {
+        match self.next_frame_time() {
+            Some(next_frame_time) if time < next_frame_time => true,
+            _ => {
+                self.last_frame_time = Some(time);
+                false
+            }
+        }
     }
 
     #[inline]
@@ -342,12 +1038,19 @@ impl Renderer {
     }
 
     pub fn resize(&mut self) -> Result<()> {
+        self.needs_redraw = true;
         let info = (*self.display_info).borrow();
         unsafe {
             self.gl
                 .Viewport(0, 0, info.adjusted_width(), info.adjusted_height());
-            self.check_error("resizing the viewport")
+            self.check_error("resizing the viewport")?;
+        }
+        if let Some(blur) = &mut self.blur {
+            blur.resize(&self.gl, info.adjusted_width(), info.adjusted_height())?;
         }
+        drop(info);
+
+        self.update_blur()
     }
 
     #[inline]
@@ -364,38 +1067,64 @@ impl Renderer {
             self.gl.ActiveTexture(gl::TEXTURE0);
             self.gl
                 .BindTexture(gl::TEXTURE_2D, self.transparent_texture);
-            self.prev_wallpaper.take();
+        }
+        // The texture is either cached or deleted
+        if let Some(prev_wallpaper) = self.prev_wallpaper.take() {
+            self.texture_cache.insert(&self.gl, prev_wallpaper);
+        }
+        // The current wallpaper does not need the wrap of its previous mode anymore
+        if self.prev_layout.take().is_some() {
+            if let Err(err) = self.set_mode(self.mode, self.offset) {
+                error!("{err:?}");
+            }
         }
     }
 
+    /// Fade the next wallpapers in from black instead of the current one
+    #[inline]
+    pub fn set_fade_from_black(&mut self, fade_from_black: bool) {
+        self.fade_from_black = fade_from_black;
+    }
+
+    /// Set how many textures of the previous wallpapers are kept in memory
+    #[inline]
+    pub fn update_texture_cache_size(&mut self, size: usize) {
+        self.texture_cache.set_capacity(&self.gl, size);
+    }
+
     #[inline]
     pub fn force_transition_end(&mut self) {
         // Force the transition to end
         self.transition_status = TransitionStatus::Ended;
+        // Draw the final frame
+        self.needs_redraw = true;
     }
 
     #[inline]
     pub fn update_transition(&mut self, transition: Transition, transform: Transform) {
-        match create_program(&self.gl, transition) {
+        match acquire_program(&self.gl, &self.share_group, transition) {
             Ok(program) => {
-                unsafe {
-                    self.gl.DeleteProgram(self.program);
-                }
+                // Deleted only if no other display uses it
+                self.share_group.release_program(&self.gl, self.program);
                 // Stop the transition immediately
                 if self.transition_running() {
                     self.force_transition_end();
                 }
                 self.program = program;
-                unsafe {
-                    if let Err(err) = self.set_projection_matrix(transform) {
-                        error!("{err:?}");
-                    }
-                }
+                // The uniforms of the new program are uploaded by the next draw
+                self.set_projection_matrix(transform);
             }
             Err(err) => error!("{err:?}"),
         }
     }
 
+    /// Returns true if the last frame drawn doesn't show the current state of the wallpaper,
+    /// i.e. the wallpaper, its mode or the display have changed, or a transition is running
+    #[inline]
+    pub fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
     #[inline]
     pub fn transition_running(&self) -> bool {
         match self.transition_status {
@@ -404,40 +1133,167 @@ impl Renderer {
         }
     }
 
-    pub unsafe fn set_projection_matrix(&self, transform: Transform) -> Result<()> {
-        let projection_matrix = projection_matrix(transform);
+    #[inline]
+    pub fn set_projection_matrix(&mut self, transform: Transform) {
+        self.projection_matrix = projection_matrix(transform);
+        self.needs_redraw = true;
+    }
+
+    /// Upload all the uniforms of the program, the renderers sharing it upload their own
+    /// before drawing
+    unsafe fn upload_uniforms(&self) -> Result<()> {
         let loc = self
             .gl
             .GetUniformLocation(self.program, b"projection_matrix\0".as_ptr() as *const _);
         self.check_error("getting the uniform location for projection_matrix")?;
         ensure!(loc > 0, "projection_matrix not found");
         self.gl
-            .UniformMatrix2fv(loc, 1, 0, projection_matrix.as_ptr());
-        //self.gl
-        //    .UniformMatrix2fv(loc, 1, 0, [1.0, 0.0, 0.0, 1.0].as_ptr());
+            .UniformMatrix2fv(loc, 1, 0, self.projection_matrix.as_ptr());
+        self.check_error("calling UniformMatrix2fv")?;
 
+        let loc = self
+            .gl
+            .GetUniformLocation(self.program, b"progress\0".as_ptr() as *const _);
+        self.check_error("getting the uniform location")?;
+        self.gl.Uniform1f(
+            loc,
+            match self.transition_status {
+                TransitionStatus::Started => 0.0,
+                TransitionStatus::Running {
+                    started: _,
+                    progress,
+                } => self.easing.apply(progress),
+                TransitionStatus::Ended => 1.0,
+            },
+        );
         self.check_error("calling Uniform1i")?;
 
+        for (name, value) in [
+            (c"brightness", self.brightness),
+            (c"contrast", self.contrast),
+            (c"gamma", self.gamma),
+            (c"vignette_strength", self.vignette_strength),
+            (c"vignette_radius", self.vignette_radius),
+            (c"opacity", self.opacity),
+        ] {
+            let loc = self.gl.GetUniformLocation(self.program, name.as_ptr());
+            self.check_error("getting the uniform location")?;
+            self.gl.Uniform1f(loc, value);
+            self.check_error("calling Uniform1f")?;
+        }
+        let loc = self.gl.GetUniformLocation(self.program, c"tint".as_ptr());
+        self.check_error("getting the uniform location")?;
+        let [red, green, blue] = self.tint;
+        self.gl.Uniform3f(loc, red, green, blue);
+        self.check_error("calling Uniform3f")?;
+
+        let layout = &self.layout;
+        let loc = self
+            .gl
+            .GetUniformLocation(self.program, b"textureScale\0".as_ptr() as *const _);
+        self.check_error("getting the uniform location")?;
+        ensure!(loc > 0, "textureScale not found");
+        self.gl
+            .Uniform2fv(loc, 1, layout.texture_scale.as_ptr() as *const _);
+        self.check_error("calling Uniform2fv on textureScale")?;
+
+        let loc = self
+            .gl
+            .GetUniformLocation(self.program, b"prevTextureScale\0".as_ptr() as *const _);
+        self.check_error("getting the uniform location")?;
+        ensure!(loc > 0, "prevTextureScale not found");
+        self.gl
+            .Uniform2fv(loc, 1, layout.prev_texture_scale.as_ptr() as *const _);
+        self.check_error("calling Uniform2fv on prevTextureScale")?;
+
+        for (name, value) in [
+            (c"texture_origin", layout.texture_origin),
+            (c"prev_texture_origin", layout.prev_texture_origin),
+            (c"display_size", layout.display_size),
+            (c"inset_scale", layout.inset_scale),
+        ] {
+            let loc = self.gl.GetUniformLocation(self.program, name.as_ptr());
+            self.check_error("getting the uniform location")?;
+            self.gl.Uniform2fv(loc, 1, value.as_ptr());
+            self.check_error("calling Uniform2fv")?;
+        }
+        for (name, value) in [
+            (c"ratio", layout.ratio),
+            (c"padding", layout.padding),
+            (c"corner_radius", layout.corner_radius),
+        ] {
+            let loc = self.gl.GetUniformLocation(self.program, name.as_ptr());
+            self.check_error("getting the uniform location")?;
+            self.gl.Uniform1f(loc, value);
+            self.check_error("calling Uniform1f")?;
+        }
+        for (name, value) in [c"prev_wrap", c"current_wrap"]
+            .into_iter()
+            .zip(layout.emulated_wraps)
+        {
+            let loc = self.gl.GetUniformLocation(self.program, name.as_ptr());
+            self.check_error("getting the uniform location")?;
+            self.gl.Uniform1i(loc, value);
+            self.check_error("calling Uniform1i")?;
+        }
+
+        // Also drawn around the wallpaper when it has padding or rounded corners
+        let loc = self
+            .gl
+            .GetUniformLocation(self.program, c"fill_color".as_ptr());
+        self.check_error("getting the uniform location for fill_color")?;
+        self.gl.Uniform4fv(loc, 1, self.border_color().as_ptr());
+        self.check_error("calling Uniform4fv")?;
+
         Ok(())
     }
 }
 
+/// Returns the program of `transition`, linked only if no other renderer of the share group
+/// has linked it yet
+fn acquire_program(
+    gl: &gl::Gl,
+    share_group: &ShareGroup,
+    transition: Transition,
+) -> Result<gl::types::GLuint> {
+    if let Some(program) = share_group.acquire_program(&transition) {
+        return Ok(program);
+    }
+    let program = create_program(gl, transition.clone())?;
+    share_group.share_program(transition, program);
+    Ok(program)
+}
+
 fn create_program(gl: &gl::Gl, transition: Transition) -> Result<gl::types::GLuint> {
+    let (uniform_callback, shader) = transition.clone().shader()?;
     unsafe {
         let program = gl.CreateProgram();
         gl_check!(gl, "calling CreateProgram");
 
-        let vertex_shader = create_shader(gl, gl::VERTEX_SHADER, &[VERTEX_SHADER_SOURCE.as_ptr()])
-            .expect("vertex shader creation succeed");
-        let (uniform_callback, shader) = transition.clone().shader();
-        let fragment_shader = create_shader(
-            gl,
-            gl::FRAGMENT_SHADER,
-            &[FRAGMENT_SHADER_SOURCE.as_ptr(), shader.as_ptr()],
-        )
-        .with_context(|| {
-            format!("unable to create fragment_shader with transisition {transition:?}")
-        })?;
+        let shaders = create_shader(gl, gl::VERTEX_SHADER, &[VERTEX_SHADER_SOURCE.as_ptr()])
+            .context("unable to create the vertex shader")
+            .and_then(|vertex_shader| {
+                create_shader(
+                    gl,
+                    gl::FRAGMENT_SHADER,
+                    &[FRAGMENT_SHADER_SOURCE.as_ptr(), shader.as_ptr()],
+                )
+                .map(|fragment_shader| (vertex_shader, fragment_shader))
+                .map_err(|err| {
+                    gl.DeleteShader(vertex_shader);
+                    err.wrap_err(format!(
+                        "unable to create fragment_shader with transisition {transition:?}"
+                    ))
+                })
+            });
+        let (vertex_shader, fragment_shader) = match shaders {
+            Ok(shaders) => shaders,
+            Err(err) => {
+                // Do not leak the objects created so far, the caller might try again
+                gl.DeleteProgram(program);
+                return Err(err);
+            }
+        };
 
         gl.AttachShader(program, vertex_shader);
         gl_check!(gl, "attach vertex shader");
@@ -477,6 +1333,215 @@ fn create_program(gl: &gl::Gl, transition: Transition) -> Result<gl::types::GLui
     }
 }
 
+/// The point of the image kept in place when it is scaled: the configured offset, or the
+/// default of `mode`
+fn texture_offset(mode: BackgroundMode, offset: Option<[f32; 2]>) -> [f32; 2] {
+    match (offset, mode) {
+        (
+            None,
+            BackgroundMode::Stretch
+            | BackgroundMode::Center
+            | BackgroundMode::Fit
+            | BackgroundMode::FitBorderColor
+            | BackgroundMode::Original,
+        ) => [0.5, 0.5],
+        (None, BackgroundMode::Tile) => [0.0, 0.0],
+        (Some(offset), _) => offset,
+    }
+}
+
+/// The wrap of the texture needed by `mode`, `wrap_mode` only applies to the modes that do
+/// not draw the fill color
+fn texture_wrap(mode: BackgroundMode, wrap_mode: Option<WrapMode>) -> gl::types::GLenum {
+    match (mode, wrap_mode) {
+        // The border of the texture draws the fill color around the wallpaper
+        (BackgroundMode::Fit | BackgroundMode::Original, _) => gl::CLAMP_TO_BORDER_EXT,
+        // The edges of the image are extended to the borders of the display
+        (BackgroundMode::FitBorderColor, _) => gl::CLAMP_TO_EDGE,
+        (_, Some(WrapMode::ClampToEdge)) => gl::CLAMP_TO_EDGE,
+        (_, Some(WrapMode::Repeat)) => gl::REPEAT,
+        (_, Some(WrapMode::MirroredRepeat)) => gl::MIRRORED_REPEAT,
+        (BackgroundMode::Tile, None) => gl::REPEAT,
+        // The texture coordinates can exceed [0, 1] by a rounding error, do not sample
+        // outside of the image
+        (BackgroundMode::Stretch | BackgroundMode::Center, None) => gl::CLAMP_TO_EDGE,
+    }
+}
+
+/// Code of a texture wrap in the fragment shader, which samples a texture clamped to its
+/// edges
+fn emulated_wrap(wrap: gl::types::GLenum) -> i32 {
+    match wrap {
+        gl::REPEAT => 1,
+        gl::MIRRORED_REPEAT => 2,
+        gl::CLAMP_TO_BORDER_EXT => 3,
+        _ => 0,
+    }
+}
+
+/// Convert a color channel from sRGB to linear
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Size of the image in the texture unit of the previous wallpaper, each wallpaper is cropped
+/// with the size of its own image
+fn prev_image_size(
+    current_wallpaper: &Wallpaper,
+    prev_wallpaper: Option<&Wallpaper>,
+    mode_change: bool,
+) -> (u32, u32) {
+    match prev_wallpaper {
+        // The current wallpaper is in both texture units
+        _ if mode_change => (
+            current_wallpaper.image_width,
+            current_wallpaper.image_height,
+        ),
+        Some(prev_wp) => (prev_wp.image_width, prev_wp.image_height),
+        None => (1, 1),
+    }
+}
+
+/// Size of the area inside of the padding, where the wallpaper is drawn
+fn inset_size(display_width: i32, display_height: i32, padding: i32) -> (i32, i32) {
+    let inset = |size: i32| size.saturating_sub(padding.saturating_mul(2)).max(1);
+    (inset(display_width), inset(display_height))
+}
+
+/// Scale of the texture coordinates that maps the area inside the padding to the whole
+/// wallpaper
+fn calculate_inset_scale(
+    display_width: i32,
+    display_height: i32,
+    inset_width: i32,
+    inset_height: i32,
+    transform: Transform,
+) -> [f32; 2] {
+    let scale = [
+        display_width as f32 / inset_width as f32,
+        display_height as f32 / inset_height as f32,
+    ];
+    // Same as calculate_texture_scale, the texture coordinates are rotated
+    match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+            [scale[1], scale[0]]
+        }
+        _ => scale,
+    }
+}
+
+/// Calculate the scale of the texture coordinates needed to draw an image into the display
+/// according to `mode`.
+///
+/// `display_width` and `display_height` are the dimensions of the buffer, as returned by
+/// [DisplayInfo::adjusted_width] and [DisplayInfo::adjusted_height]. The projection matrix
+/// rotates the wallpaper by `transform`, so the ratio is calculated on the display as seen by
+/// the user, i.e. width and height are swapped back for the rotated transforms.
+pub fn calculate_texture_scale(
+    mode: BackgroundMode,
+    display_width: i32,
+    display_height: i32,
+    transform: Transform,
+    image_width: u32,
+    image_height: u32,
+) -> [f32; 2] {
+    let (display_width, display_height) = match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+            (display_height as f32, display_width as f32)
+        }
+        _ => (display_width as f32, display_height as f32),
+    };
+    let display_ratio = display_width / display_height;
+    let (image_width, image_height) = (image_width as f32, image_height as f32);
+    let image_ratio: f32 = image_width / image_height;
+    match mode {
+        BackgroundMode::Stretch => [1.0, 1.0],
+        BackgroundMode::Center => [
+            (display_ratio / image_ratio).min(1.0),
+            (image_ratio / display_ratio).min(1.0),
+        ],
+        BackgroundMode::Fit | BackgroundMode::FitBorderColor => {
+            // Portrait mode
+            // In this case we calculate the width relative to the height of the
+            // screen with the ratio of the image
+            let width = display_height * image_ratio;
+            // Same thing as above, just with the width
+            let height = display_width / image_ratio;
+            // Then we calculate the proportions
+            [
+                (display_width / width).max(1.0),
+                (display_height / height).max(1.0),
+            ]
+        }
+        BackgroundMode::Original => {
+            // Draw the image pixel by pixel, the texture coordinates outside of the
+            // image will show the border color
+            [display_width / image_width, display_height / image_height]
+        }
+        BackgroundMode::Tile => {
+            let width_proportion = display_width / image_width * display_ratio;
+            let height_proportion = display_height / image_height * display_ratio;
+            if display_ratio > image_ratio {
+                // Portrait mode
+                if height_proportion.max(1.0) == 1.0 {
+                    // Same as Fit
+                    let width = display_height * image_ratio;
+                    [display_width / width, 1.0]
+                } else {
+                    [width_proportion, height_proportion]
+                }
+            } else {
+                // Landscape mode
+                if width_proportion.max(1.0) == 1.0 {
+                    // Same as Fit
+                    let height = display_width / image_ratio;
+                    [1.0, display_height / height]
+                } else {
+                    [width_proportion, height_proportion]
+                }
+            }
+        }
+    }
+}
+
+/// The part of the image shown inside of the padding, as texture coordinates; the
+/// coordinates outside of 0.0 and 1.0 show the border or repeat the image, depending on the
+/// mode. `inset_size` is the size of the area inside of the padding, as returned by
+/// [inset_size]. Tiles are scaled by `tile_scale` and the center mode is zoomed by `ken_burns`
+pub fn calculate_texture_coordinates(
+    mode: BackgroundMode,
+    offset: Option<[f32; 2]>,
+    (inset_width, inset_height): (i32, i32),
+    transform: Transform,
+    (image_width, image_height): (u32, u32),
+    tile_scale: f32,
+    ken_burns: Option<&KenBurns>,
+) -> Coordinates {
+    let texture_scale = calculate_texture_scale(
+        mode,
+        inset_width,
+        inset_height,
+        transform,
+        image_width,
+        image_height,
+    );
+    let offset = texture_offset(mode, offset);
+    let (texture_scale, offset) = match (mode, ken_burns) {
+        // Bigger tiles means less repetitions of the image
+        (BackgroundMode::Tile, _) => (texture_scale.map(|scale| scale / tile_scale), offset),
+        (BackgroundMode::Center, Some(ken_burns)) => (
+            texture_scale.map(|scale| scale / ken_burns.zoom()),
+            ken_burns.offset(offset),
+        ),
+        _ => (texture_scale, offset),
+    };
+    Coordinates::default_texture_coordinates().scale(texture_scale, offset)
+}
+
 #[rustfmt::skip]
 fn projection_matrix(transform: Transform) -> [f32; 4] {
     match transform {
@@ -542,14 +1607,256 @@ impl Deref for Renderer {
 
 impl Drop for Renderer {
     fn drop(&mut self) {
+        // The shared objects are only deleted when no other display uses them
+        self.share_group
+            .release_texture(&self.gl, &self.current_wallpaper);
+        if let Some(wp) = &self.prev_wallpaper {
+            self.share_group.release_texture(&self.gl, wp);
+        }
+        // The cache never contains the current and the previous wallpapers
+        self.texture_cache.clear(&self.gl);
+        self.share_group.release_program(&self.gl, self.program);
         unsafe {
-            self.gl.DeleteTextures(1, &self.current_wallpaper.texture);
-            if let Some(wp) = &self.prev_wallpaper {
-                self.gl.DeleteTextures(1, &wp.texture);
-            }
+            self.gl.DeleteTextures(1, &self.transparent_texture);
+            self.gl.DeleteTextures(1, &self.black_texture);
+            self.gl.DeleteSamplers(2, self.samplers.as_ptr());
             self.gl.DeleteBuffers(1, &self.eab);
             self.gl.DeleteBuffers(1, &self.vbo);
-            self.gl.DeleteProgram(self.program);
         }
+        if let Some(blur) = &self.blur {
+            blur.delete(&self.gl);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [BackgroundMode; 6] = [
+        BackgroundMode::Stretch,
+        BackgroundMode::Center,
+        BackgroundMode::Fit,
+        BackgroundMode::Tile,
+        BackgroundMode::FitBorderColor,
+        BackgroundMode::Original,
+    ];
+
+    #[test]
+    fn test_transition_status_without_time() {
+        // A draw outside of a frame callback does not start the transition
+        let status = TransitionStatus::Started.advance(None, 300);
+        assert_eq!(TransitionStatus::Started, status);
+        let status = status.advance(Some(100_000), 300);
+        assert_eq!(
+            TransitionStatus::Running {
+                started: 100_000,
+                progress: 0.0
+            },
+            status
+        );
+        // Nor moves it
+        assert_eq!(status, status.advance(None, 300));
+        assert_eq!(
+            TransitionStatus::Running {
+                started: 100_000,
+                progress: 0.5
+            },
+            status.advance(Some(100_150), 300)
+        );
+        assert_eq!(TransitionStatus::Ended, status.advance(Some(100_300), 300));
+    }
+
+    #[test]
+    fn test_texture_scale_rotated() {
+        // A 1920x1080 display rotated by 90 degrees has a 1080x1920 buffer, the wallpaper
+        // must look the same as in a 1920x1080 display that hasn't been rotated
+        for mode in MODES {
+            assert_eq!(
+                calculate_texture_scale(mode, 1920, 1080, Transform::Normal, 1600, 1200),
+                calculate_texture_scale(mode, 1080, 1920, Transform::_90, 1600, 1200),
+            );
+            assert_eq!(
+                calculate_texture_scale(mode, 1920, 1080, Transform::Flipped180, 1600, 1200),
+                calculate_texture_scale(mode, 1080, 1920, Transform::Flipped270, 1600, 1200),
+            );
+        }
+    }
+
+    #[test]
+    fn test_texture_coordinates_center() {
+        // A 4:3 image fills a 16:9 display by cutting its top and bottom
+        assert_eq!(
+            Coordinates::new(0.0, 1.0, 0.125, 0.875),
+            calculate_texture_coordinates(
+                BackgroundMode::Center,
+                None,
+                (1920, 1080),
+                Transform::Normal,
+                (1600, 1200),
+                1.0,
+                None
+            )
+        );
+        // Anchor the image to one side
+        assert_eq!(
+            Coordinates::new(0.0, 1.0, 0.0, 0.75),
+            calculate_texture_coordinates(
+                BackgroundMode::Center,
+                Some([0.5, 0.0]),
+                (1920, 1080),
+                Transform::Normal,
+                (1600, 1200),
+                1.0,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_texture_coordinates_transition() {
+        let prev_wallpaper = Wallpaper {
+            image_width: 1600,
+            image_height: 1200,
+            ..Wallpaper::new()
+        };
+        let current_wallpaper = Wallpaper {
+            image_width: 3840,
+            image_height: 1080,
+            ..Wallpaper::new()
+        };
+        // During a transition each wallpaper is cropped using the size of its own image
+        let prev = calculate_texture_coordinates(
+            BackgroundMode::Center,
+            None,
+            (1920, 1080),
+            Transform::Normal,
+            prev_image_size(&current_wallpaper, Some(&prev_wallpaper), false),
+            1.0,
+            None,
+        );
+        let current = calculate_texture_coordinates(
+            BackgroundMode::Center,
+            None,
+            (1920, 1080),
+            Transform::Normal,
+            (3840, 1080),
+            1.0,
+            None,
+        );
+        assert_eq!(Coordinates::new(0.0, 1.0, 0.125, 0.875), prev);
+        assert_eq!(Coordinates::new(0.25, 0.75, 0.0, 1.0), current);
+        // While the mode change is animated both texture units show the current wallpaper
+        assert_eq!(
+            (3840, 1080),
+            prev_image_size(&current_wallpaper, Some(&prev_wallpaper), true)
+        );
+    }
+
+    #[test]
+    fn test_texture_coordinates_tile() {
+        let tiles = calculate_texture_coordinates(
+            BackgroundMode::Tile,
+            None,
+            (1920, 1080),
+            Transform::Normal,
+            (800, 600),
+            1.0,
+            None,
+        );
+        let bigger_tiles = calculate_texture_coordinates(
+            BackgroundMode::Tile,
+            None,
+            (1920, 1080),
+            Transform::Normal,
+            (800, 600),
+            2.0,
+            None,
+        );
+        // Tiles twice as big show half of the repetitions, starting from the same corner
+        assert_eq!(tiles.size().map(|size| size / 2.0), bigger_tiles.size());
+        assert_eq!([0.0, 0.0], bigger_tiles.origin());
+    }
+
+    #[test]
+    fn test_inset_size() {
+        // The wallpaper is laid out in the area inside of the padding
+        assert_eq!((1800, 960), inset_size(1920, 1080, 60));
+        // Even when the padding is bigger than the display
+        assert_eq!((1, 1), inset_size(1920, 1080, i32::MAX));
+    }
+
+    #[test]
+    fn test_texture_wrap_fit() {
+        // The fill color around the wallpaper does not depend on the wrap mode
+        for wrap_mode in [
+            None,
+            Some(WrapMode::ClampToEdge),
+            Some(WrapMode::Repeat),
+            Some(WrapMode::MirroredRepeat),
+        ] {
+            assert_eq!(
+                gl::CLAMP_TO_BORDER_EXT,
+                texture_wrap(BackgroundMode::Fit, wrap_mode)
+            );
+            assert_eq!(
+                gl::CLAMP_TO_BORDER_EXT,
+                texture_wrap(BackgroundMode::Original, wrap_mode)
+            );
+            assert_eq!(
+                gl::CLAMP_TO_EDGE,
+                texture_wrap(BackgroundMode::FitBorderColor, wrap_mode)
+            );
+        }
+        assert_eq!(gl::REPEAT, texture_wrap(BackgroundMode::Tile, None));
+        assert_eq!(
+            gl::CLAMP_TO_EDGE,
+            texture_wrap(BackgroundMode::Center, None)
+        );
+    }
+
+    #[test]
+    fn test_texture_coordinates_stretch() {
+        assert_eq!(
+            Coordinates::default_texture_coordinates(),
+            calculate_texture_coordinates(
+                BackgroundMode::Stretch,
+                None,
+                (1920, 1080),
+                Transform::Normal,
+                (1600, 1200),
+                1.0,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_texture_scale_center_rotated() {
+        // A square image in a landscape display is cut vertically
+        assert_eq!(
+            [1.0, 0.5625],
+            calculate_texture_scale(
+                BackgroundMode::Center,
+                1920,
+                1080,
+                Transform::Normal,
+                1000,
+                1000
+            )
+        );
+        // The same buffer rotated by 270 degrees is a portrait display, hence the image is
+        // cut horizontally
+        assert_eq!(
+            [0.5625, 1.0],
+            calculate_texture_scale(
+                BackgroundMode::Center,
+                1920,
+                1080,
+                Transform::_270,
+                1000,
+                1000
+            )
+        );
     }
 }
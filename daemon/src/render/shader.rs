@@ -82,20 +82,143 @@ layout (location = 5) uniform sampler2D u_texture;
 
 uniform float progress;
 uniform float ratio;
-uniform float texture_offset;
+// The texture coordinates of the bottom left corner of the wallpaper, the scales above
+// are the size of the texture coordinates shown
+uniform vec2 texture_origin;
+uniform vec2 prev_texture_origin;
+
+uniform float brightness;
+uniform float contrast;
+uniform float gamma;
+// Multiplier of each color channel, i.e. to warm up the wallpaper in the evening
+uniform vec3 tint;
+
+// Darkening of the edges, relative to the distance from the center of the display
+uniform float vignette_strength;
+uniform float vignette_radius;
+
+// Multiplier of the whole output, which is premultiplied by its alpha like the one read by
+// the compositor
+uniform float opacity;
+
+// Size of the framebuffer, the padding and the corners radius are in its pixels
+uniform vec2 display_size;
+uniform vec2 inset_scale;
+uniform float padding;
+uniform float corner_radius;
+uniform vec4 fill_color;
+
+// Wrap of each texture unit while both of them show the same texture to animate a mode
+// change: 1 repeats, 2 mirrors, 3 draws the fill color and 0 leaves it to the texture
+uniform int prev_wrap;
+uniform int current_wrap;
 
 vec4 transition(vec2);
 
+vec4 sampleWrapped(sampler2D tex, vec2 uv, int wrap) {
+    if (wrap == 0) {
+        return texture(tex, uv);
+    }
+    if (wrap == 3) {
+        return any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))
+            ? fill_color : texture(tex, uv);
+    }
+    vec2 wrapped = wrap == 1 ? fract(uv) : 1.0 - abs(mod(uv, 2.0) - 1.0);
+    // Keep the derivatives of the continuous coordinates, or the seams select the
+    // smallest mipmap
+    return textureGrad(tex, wrapped, dFdx(uv), dFdy(uv));
+}
+
+vec4 adjustColor(vec4 color) {
+    // Leave the color untouched with the default values
+    if (brightness == 1.0 && contrast == 1.0 && gamma == 1.0 && tint == vec3(1.0)) {
+        return color;
+    }
+    vec3 rgb = color.rgb * brightness;
+    rgb = (rgb - 0.5) * contrast + 0.5;
+    rgb = pow(clamp(rgb, 0.0, 1.0), vec3(1.0 / gamma));
+    return vec4(rgb * tint, color.a);
+}
+
+// Applied after the transition, so that the vignette does not change between the wallpapers
+vec4 vignette(vec4 color) {
+    if (vignette_strength == 0.0) {
+        return color;
+    }
+    // 0.0 in the center of the display and 1.0 in its corners
+    float distance = length(gl_FragCoord.xy / display_size - 0.5) * sqrt(2.0);
+    float falloff = smoothstep(vignette_radius, 1.0, distance);
+    return vec4(color.rgb * (1.0 - vignette_strength * falloff), color.a);
+}
+
 vec4 getFromColor(vec2 uv) {
-    uv = (uv - texture_offset) * prevTextureScale + (texture_offset);
-    return texture(u_prev_texture, uv);
+    uv = uv * prevTextureScale + prev_texture_origin;
+    return sampleWrapped(u_prev_texture, uv, prev_wrap);
 }
 
 vec4 getToColor(vec2 uv) {
-    uv = (uv - texture_offset) * textureScale + (texture_offset);
-    return texture(u_texture, uv);
+    uv = uv * textureScale + texture_origin;
+    return sampleWrapped(u_texture, uv, current_wrap);
 }
 
 void main() {
-    FragColor = transition(v_texcoord);
+    if (padding == 0.0 && corner_radius == 0.0) {
+        FragColor = vignette(adjustColor(transition(v_texcoord)));
+        FragColor *= opacity;
+        return;
+    }
+    // Signed distance from the edge of the rounded rectangle, negative inside of it
+    vec2 half_size = display_size * 0.5 - padding;
+    vec2 q = abs(gl_FragCoord.xy - display_size * 0.5) - half_size + corner_radius;
+    float distance = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - corner_radius;
+    // The whole wallpaper is drawn inside of the padding
+    vec2 uv = (v_texcoord - 0.5) * inset_scale + 0.5;
+    vec4 color = vignette(adjustColor(transition(uv)));
+    // Smooth the edge over a pixel
+    FragColor = mix(color, fill_color, clamp(distance + 0.5, 0.0, 1.0));
+    FragColor *= opacity;
+}";
+
+/// Draw the whole framebuffer: the texture coordinates follow the position of the
+/// vertices, the projection has already been applied when drawing the wallpaper
+pub const BLUR_VERTEX_SHADER_SOURCE: &CStr = c"
+#version 320 es
+precision mediump float;
+
+layout (location = 0) in vec2 aPosition;
+
+out vec2 v_texcoord;
+
+void main() {
+    gl_Position = vec4(aPosition, 1.0, 1.0);
+    v_texcoord = aPosition * 0.5 + 0.5;
+}";
+
+/// One pass of the separable gaussian blur, along `direction`
+pub const BLUR_FRAGMENT_SHADER_SOURCE: &CStr = c"
+#version 320 es
+precision mediump float;
+out vec4 FragColor;
+
+in vec2 v_texcoord;
+
+uniform sampler2D u_texture;
+// The size of a pixel along the direction of the blur
+uniform vec2 direction;
+uniform float sigma;
+
+// Limit the number of samples taken for each pixel
+const int MAX_RADIUS = 64;
+
+void main() {
+    int radius = min(int(ceil(sigma * 3.0)), MAX_RADIUS);
+    vec4 color = texture(u_texture, v_texcoord);
+    float total = 1.0;
+    for (int i = 1; i <= radius; i++) {
+        float weight = exp(-float(i * i) / (2.0 * sigma * sigma));
+        color += texture(u_texture, v_texcoord + direction * float(i)) * weight;
+        color += texture(u_texture, v_texcoord - direction * float(i)) * weight;
+        total += 2.0 * weight;
+    }
+    FragColor = color / total;
 }";
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+
+use crate::image_loader::ImageIdentity;
+
+use super::{gl, wallpaper::Wallpaper, TextureOptions, Transition};
+
+/// The openGL objects shared by the renderers whose contexts are in the same share group:
+/// the programs of the transitions and the textures of the static wallpapers.
+///
+/// Each object counts the renderers using it and is deleted by the last one releasing it,
+/// so that it is deleted once and never while another display still draws with it. The
+/// renderers release their objects with their own context current, which is in the group.
+#[derive(Default)]
+pub struct ShareGroup {
+    programs: RefCell<Vec<SharedProgram>>,
+    textures: RefCell<Vec<SharedTexture>>,
+}
+
+struct SharedProgram {
+    transition: Transition,
+    program: gl::types::GLuint,
+    users: usize,
+}
+
+struct SharedTexture {
+    identity: ImageIdentity,
+    options: TextureOptions,
+    texture: gl::types::GLuint,
+    image_width: u32,
+    image_height: u32,
+    users: usize,
+}
+
+impl ShareGroup {
+    /// Returns the program of `transition` if a renderer of the group has already linked it,
+    /// counting one more user
+    pub fn acquire_program(&self, transition: &Transition) -> Option<gl::types::GLuint> {
+        let mut programs = self.programs.borrow_mut();
+        let shared = programs
+            .iter_mut()
+            .find(|shared| shared.transition == *transition)?;
+        shared.users += 1;
+        Some(shared.program)
+    }
+
+    /// Share a program that has just been linked, its renderer is the first user
+    pub fn share_program(&self, transition: Transition, program: gl::types::GLuint) {
+        self.programs.borrow_mut().push(SharedProgram {
+            transition,
+            program,
+            users: 1,
+        });
+    }
+
+    /// Stop using `program`, it is deleted when no other renderer uses it
+    pub fn release_program(&self, gl: &gl::Gl, program: gl::types::GLuint) {
+        let mut programs = self.programs.borrow_mut();
+        if let Some(index) = programs.iter().position(|shared| shared.program == program) {
+            programs[index].users -= 1;
+            if programs[index].users > 0 {
+                return;
+            }
+            programs.swap_remove(index);
+        }
+        unsafe {
+            gl.DeleteProgram(program);
+        }
+    }
+
+    /// Returns true when a renderer of the group has loaded the image `identity` with the
+    /// same texels as `options`
+    pub fn contains_texture(&self, identity: &ImageIdentity, options: TextureOptions) -> bool {
+        self.textures
+            .borrow()
+            .iter()
+            .any(|shared| shared.identity == *identity && shared.options.same_texels(&options))
+    }
+
+    /// Returns the wallpaper using the texture of the image `identity`, counting one more user
+    pub fn acquire_texture(
+        &self,
+        identity: &ImageIdentity,
+        options: TextureOptions,
+    ) -> Option<Wallpaper> {
+        let mut textures = self.textures.borrow_mut();
+        let shared = textures
+            .iter_mut()
+            .find(|shared| shared.identity == *identity && shared.options.same_texels(&options))?;
+        shared.users += 1;
+        Some(Wallpaper {
+            texture: shared.texture,
+            image_width: shared.image_width,
+            image_height: shared.image_height,
+            identity: Some(identity.clone()),
+        })
+    }
+
+    /// Share the texture of a static wallpaper that has just been loaded, its renderer is
+    /// the first user. The textures of the animated wallpapers change with every frame and
+    /// are never shared
+    pub fn share_texture(&self, wallpaper: &Wallpaper, options: TextureOptions) {
+        let Some(identity) = wallpaper.identity.clone() else {
+            return;
+        };
+        self.textures.borrow_mut().push(SharedTexture {
+            identity,
+            options,
+            texture: wallpaper.texture,
+            image_width: wallpaper.image_width,
+            image_height: wallpaper.image_height,
+            users: 1,
+        });
+    }
+
+    /// Stop using the texture of `wallpaper`, it is deleted when no other renderer uses it.
+    /// The texture is found even if the wallpaper has forgotten its identity
+    pub fn release_texture(&self, gl: &gl::Gl, wallpaper: &Wallpaper) {
+        let mut textures = self.textures.borrow_mut();
+        if let Some(index) = textures
+            .iter()
+            .position(|shared| shared.texture == wallpaper.texture)
+        {
+            textures[index].users -= 1;
+            if textures[index].users > 0 {
+                return;
+            }
+            textures.swap_remove(index);
+        }
+        unsafe {
+            gl.DeleteTextures(1, &wallpaper.texture);
+        }
+    }
+}
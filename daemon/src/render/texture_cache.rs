@@ -0,0 +1,79 @@
+use std::{collections::VecDeque, rc::Rc};
+
+use crate::image_loader::ImageIdentity;
+
+use super::{gl, wallpaper::Wallpaper, ShareGroup};
+
+/// Textures of the wallpapers shown recently, so that they can be shown again without
+/// decoding and uploading the image one more time
+///
+/// The cache uses the textures it contains: the wallpapers taken out of it are used by the
+/// renderer again, and the others are released to the share group, which deletes each
+/// texture once no renderer uses it.
+pub struct TextureCache {
+    /// Maximum number of textures kept, 0 disables the cache
+    capacity: usize,
+    /// The least recently used wallpaper comes first
+    wallpapers: VecDeque<Wallpaper>,
+    share_group: Rc<ShareGroup>,
+}
+
+impl TextureCache {
+    pub fn new(share_group: Rc<ShareGroup>) -> Self {
+        Self {
+            capacity: 0,
+            wallpapers: VecDeque::new(),
+            share_group,
+        }
+    }
+
+    pub fn contains(&self, identity: &ImageIdentity) -> bool {
+        self.wallpapers
+            .iter()
+            .any(|wallpaper| wallpaper.identity.as_ref() == Some(identity))
+    }
+
+    /// Remove the wallpaper from the cache, the caller now uses its texture
+    pub fn take(&mut self, identity: &ImageIdentity) -> Option<Wallpaper> {
+        let index = self
+            .wallpapers
+            .iter()
+            .position(|wallpaper| wallpaper.identity.as_ref() == Some(identity))?;
+        self.wallpapers.remove(index)
+    }
+
+    /// Keep the texture of a wallpaper that is not shown anymore. Its texture is released
+    /// right away when it cannot be cached, i.e. for animated wallpapers
+    pub fn insert(&mut self, gl: &gl::Gl, wallpaper: Wallpaper) {
+        let Some(identity) = wallpaper.identity.as_ref().filter(|_| self.capacity > 0) else {
+            self.share_group.release_texture(gl, &wallpaper);
+            return;
+        };
+        // Keep a single texture for each image, the most recent one
+        if let Some(cached) = self.take(identity) {
+            self.share_group.release_texture(gl, &cached);
+        }
+        self.wallpapers.push_back(wallpaper);
+        self.evict(gl);
+    }
+
+    pub fn set_capacity(&mut self, gl: &gl::Gl, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(gl);
+    }
+
+    /// Release all the textures, i.e. when they have been loaded with different settings
+    pub fn clear(&mut self, gl: &gl::Gl) {
+        for wallpaper in self.wallpapers.drain(..) {
+            self.share_group.release_texture(gl, &wallpaper);
+        }
+    }
+
+    fn evict(&mut self, gl: &gl::Gl) {
+        while self.wallpapers.len() > self.capacity {
+            if let Some(wallpaper) = self.wallpapers.pop_front() {
+                self.share_group.release_texture(gl, &wallpaper);
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use std::ffi::CStr;
+use std::{
+    borrow::Cow,
+    ffi::{CStr, CString},
+    path::PathBuf,
+};
 
 use color_eyre::{
-    eyre::{bail, ensure},
+    eyre::{bail, ensure, Context},
     Result,
 };
 use serde::Deserialize;
@@ -94,17 +98,49 @@ impl UniformSetter for [f32; 4] {
     }
 }
 
+/// Duration in milliseconds of a custom transition when `transition-time` is not set, the
+/// same as the default fade transition
+const CUSTOM_TRANSITION_TIME: u32 = 300;
+
 macro_rules! transition_shader {
     ($enum:ident { $($variant:ident { $($field_name:ident: $field_ty:ty = ($glsl_name:literal, $default_value:expr)),* } => $default_time:expr),* }) => {
         #[derive(Deserialize, Clone, Debug, PartialEq)]
         #[serde(rename_all = "kebab-case", rename_all_fields = "kebab-case", deny_unknown_fields)]
         pub enum $enum {
-            $($variant { $($field_name: Option<$field_ty>),* }),*
+            $($variant { $($field_name: Option<$field_ty>),* },)*
+            /// A transition written by the user, loaded from a GLSL file
+            Custom {
+                #[serde(deserialize_with = "crate::config::tilde_expansion_path_deserialize")]
+                path: PathBuf,
+            },
         }
 
         impl $enum {
-            pub fn shader(self) -> (Box<UniformCallback>, &'static CStr) {
-                match self {
+            pub fn shader(self) -> Result<(Box<UniformCallback>, Cow<'static, CStr>)> {
+                Ok(match self {
+                    $enum::Custom { path } => {
+                        let source = std::fs::read_to_string(&path).with_context(|| {
+                            format!("unable to read the custom transition {path:?}")
+                        })?;
+                        let source = CString::new(source).with_context(|| {
+                            format!("the custom transition {path:?} contains a NUL byte")
+                        })?;
+                        (
+                            Box::new(|gl: &gl::Gl, program| {
+                                unsafe {
+                                    let loc = gl.GetUniformLocation(program, c"progress".as_ptr());
+                                    gl_check!(gl, "getting the uniform location for progress");
+                                    // The compiler removes the uniforms that are not used
+                                    ensure!(
+                                        loc >= 0,
+                                        "the custom transition must use the progress uniform"
+                                    );
+                                }
+                                Ok(())
+                            }) as Box<UniformCallback>,
+                            Cow::Owned(source),
+                        )
+                    }
                     //$($enum::$variant => (
                     //    Box::new(|_, _| Ok(())),
                     //    include_cstr!(concat!("shaders/", stringify!($variant), ".glsl")),
@@ -123,14 +159,15 @@ macro_rules! transition_shader {
                             )*
                             Ok(())
                         }),
-                        include_cstr!(concat!("shaders/", stringify!($variant), ".glsl"))
+                        Cow::Borrowed(include_cstr!(concat!("shaders/", stringify!($variant), ".glsl")))
                     ),)*
-                }
+                })
             }
 
             pub const fn default_transition_time(&self) -> u32 {
                 match self {
                     $($enum::$variant { .. } => $default_time,)*
+                    $enum::Custom { .. } => CUSTOM_TRANSITION_TIME,
                 }
             }
         }
@@ -1,20 +1,21 @@
 use std::ffi::CStr;
 
+use crate::{gl_check, image_loader::ImageIdentity, render::gl};
 use color_eyre::{
     eyre::{bail, ensure},
     Result,
 };
-use image::DynamicImage;
 
-use crate::{gl_check, render::gl};
-
-use super::load_texture;
+use super::{load_texture, Pixels, TextureOptions};
 
 #[derive(Default)]
 pub struct Wallpaper {
     pub texture: gl::types::GLuint,
     pub image_width: u32,
     pub image_height: u32,
+    /// The image loaded in the texture, only set for static images so that they can be
+    /// cached
+    pub identity: Option<ImageIdentity>,
 }
 
 impl Wallpaper {
@@ -23,6 +24,7 @@ impl Wallpaper {
             texture: 0,
             image_width: 10,
             image_height: 10,
+            identity: None,
         }
     }
 
@@ -35,14 +37,23 @@ impl Wallpaper {
         Ok(())
     }
 
-    pub fn load_image(&mut self, gl: &gl::Gl, image: DynamicImage) -> Result<()> {
-        self.image_width = image.width();
-        self.image_height = image.height();
+    pub fn load_image(
+        &mut self,
+        gl: &gl::Gl,
+        pixels: Pixels,
+        (image_width, image_height): (u32, u32),
+        options: TextureOptions,
+    ) -> Result<()> {
+        // Keep the size of the image file even when `pixels` has been scaled down, so that
+        // its ratio and the original mode stay the same
+        self.image_width = image_width;
+        self.image_height = image_height;
 
-        let texture = load_texture(gl, image)?;
+        let texture = load_texture(gl, pixels, options)?;
 
         unsafe {
-            // Delete from memory the previous texture
+            // Delete from memory the previous texture, only the frames of the animated
+            // wallpapers replace a texture and they are never shared
             gl.DeleteTextures(1, &self.texture);
         }
         self.texture = texture;
@@ -1,13 +1,12 @@
 use std::{
     cell::RefCell,
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
     time::{Duration, Instant},
 };
 
 use color_eyre::eyre::{Context, ContextCompat};
 use color_eyre::Result;
-use image::RgbaImage;
 use log::{error, warn};
 use smithay_client_toolkit::reexports::calloop::{LoopHandle, RegistrationToken};
 use smithay_client_toolkit::reexports::client::protocol::wl_output::{Transform, WlOutput};
@@ -21,8 +20,14 @@ use smithay_client_toolkit::{
 
 use crate::render::{EglContext, Renderer};
 use crate::wpaperd::Wpaperd;
-use crate::{display_info::DisplayInfo, wallpaper_info::WallpaperInfo};
-use crate::{image_loader::ImageLoader, image_picker::ImagePicker};
+use crate::{
+    display_info::DisplayInfo,
+    wallpaper_info::{BackgroundMode, ColorSource, WallpaperInfo},
+};
+use crate::{
+    image_loader::{ImageData, ImageFrames, ImageIdentity, ImageLoader},
+    image_picker::ImagePicker,
+};
 
 #[derive(Debug)]
 pub enum EventSource {
@@ -33,11 +38,13 @@ pub enum EventSource {
 }
 
 pub struct Surface {
+    // Dropped in this order: the renderer deletes its objects in its context, which is
+    // destroyed before the wayland surface
+    renderer: Renderer,
+    egl_context: EglContext,
     wl_surface: wl_surface::WlSurface,
     wl_output: WlOutput,
     layer: LayerSurface,
-    egl_context: EglContext,
-    renderer: Renderer,
     pub image_picker: ImagePicker,
     event_source: EventSource,
     pub wallpaper_info: WallpaperInfo,
@@ -55,6 +62,11 @@ pub struct Surface {
     /// Setting this to true will mean only an explicit next/previous wallpaper command will change
     /// the wallpaper.
     should_pause: bool,
+    /// The color currently drawn, when the wallpaper is not an image
+    loaded_color_source: Option<ColorSource>,
+    event_loop_handle: LoopHandle<'static, Wpaperd>,
+    /// Timer requesting the next frame callback, armed when the next frame is not due yet
+    frame_timer: Option<RegistrationToken>,
 }
 
 impl Surface {
@@ -66,13 +78,21 @@ impl Surface {
         wallpaper_info: WallpaperInfo,
         egl_display: egl::Display,
         qh: &QueueHandle<Wpaperd>,
-    ) -> Self {
+    ) -> Result<Self> {
         let wl_surface = wl_layer.wl_surface().clone();
-        let egl_context = EglContext::new(egl_display, &wl_surface);
+        let egl_context = EglContext::new(
+            egl_display,
+            &wl_surface,
+            wallpaper_info.srgb,
+            wallpaper_info.bit_depth,
+            &mut wpaperd.egl_share_contexts.borrow_mut(),
+        )?;
         // Make the egl context as current to make the renderer creation work
-        egl_context
-            .make_current()
-            .expect("EGL context switching to work");
+        egl_context.make_current()?;
+        // Not worth leaving the display without a wallpaper
+        if let Err(err) = egl_context.set_swap_interval(wallpaper_info.swap_interval) {
+            error!("{err:?}");
+        }
 
         // Commit the surface
         wl_surface.commit();
@@ -84,19 +104,46 @@ impl Surface {
             wpaperd.wallpaper_groups.clone(),
         );
 
-        let image = black_image();
         let info = Rc::new(RefCell::new(info));
 
-        let renderer = unsafe {
+        let mut renderer = unsafe {
             Renderer::new(
-                image.into(),
+                egl_context.share_group(),
                 info.clone(),
                 0,
                 wallpaper_info.transition.clone(),
                 info.borrow().transform,
+                wallpaper_info.fill_color,
+                wallpaper_info.texture_filter,
             )
-            .expect("unable to create the renderer")
+            .context("unable to create the renderer")?
         };
+        renderer.update_srgb(egl_context.srgb())?;
+        renderer.update_deep_color(egl_context.deep_color())?;
+        renderer.update_premultiply_alpha(wallpaper_info.premultiply_alpha)?;
+        renderer.update_mipmaps(wallpaper_info.mipmaps)?;
+        renderer.update_anisotropy(wallpaper_info.anisotropy)?;
+        renderer.update_tile_scale(wallpaper_info.tile_scale)?;
+        renderer.update_wrap_mode(wallpaper_info.wrap_mode)?;
+        renderer.update_padding(wallpaper_info.padding, wallpaper_info.corner_radius)?;
+        renderer.update_ken_burns(wallpaper_info.ken_burns_zoom, wallpaper_info.ken_burns_time)?;
+        renderer.update_flip(wallpaper_info.flip_horizontal, wallpaper_info.flip_vertical)?;
+        renderer.set_easing(wallpaper_info.easing);
+        renderer.set_fade_from_black(wallpaper_info.fade_from_black);
+        renderer.update_max_fps(wallpaper_info.max_fps);
+        renderer.update_texture_cache_size(wallpaper_info.texture_cache_size);
+        renderer.set_brightness(wallpaper_info.brightness);
+        renderer.set_contrast(wallpaper_info.contrast);
+        renderer.set_gamma(wallpaper_info.gamma);
+        renderer.set_tint(wallpaper_info.tint);
+        renderer.set_vignette(
+            wallpaper_info.vignette_strength,
+            wallpaper_info.vignette_radius,
+        );
+        renderer.set_opacity(wallpaper_info.opacity);
+        if let Err(err) = renderer.set_blur(wallpaper_info.blur) {
+            error!("{err:?}");
+        }
 
         let first_transition = !wallpaper_info.initial_transition;
         let mut surface = Self {
@@ -115,6 +162,9 @@ impl Surface {
             loading_image: None,
             loading_image_tries: 0,
             skip_next_transition: first_transition,
+            loaded_color_source: None,
+            event_loop_handle: wpaperd.event_loop_handle.clone(),
+            frame_timer: None,
         };
 
         // Start loading the wallpaper as soon as possible (i.e. surface creation)
@@ -123,7 +173,7 @@ impl Surface {
             warn!("{err:?}");
         }
 
-        surface
+        Ok(surface)
     }
 
     /// Returns true if something has been drawn to the surface
@@ -139,15 +189,28 @@ impl Surface {
 
         let wallpaper_loaded = self.load_wallpaper(qh)?;
 
+        if let Some(time) = time {
+            self.renderer.update_animation(time)?;
+        }
+
         if self.renderer.transition_running() {
             // Recalculate the current progress, the transition might end now
-            let transition_running = self.renderer.update_transition_status(time.unwrap_or(0));
-            // If we don't have any time passed, just consider the transition to be ended
+            let transition_running = self.renderer.update_transition_status(time);
             if transition_running {
-                // Don't call queue_draw as it calls load_wallpaper again
-                self.wl_surface.frame(qh, self.wl_surface.clone());
+                let skip_frame = self.window_drawn && self.renderer.skip_frame(time.unwrap_or(0));
+                // Don't call queue_draw as it calls load_wallpaper again. Wait for the next
+                // frame allowed by max-fps
+                self.request_frame(qh, time, self.renderer.next_frame_time());
+                if skip_frame {
+                    self.wl_surface().commit();
+                    return Ok(());
+                }
             } else {
                 self.renderer.transition_finished();
+                if self.renderer.is_animated() {
+                    // Keep playing the animation
+                    self.wl_surface.frame(qh, self.wl_surface.clone());
+                }
             }
         } else if !wallpaper_loaded {
             self.wl_surface.frame(qh, self.wl_surface.clone());
@@ -156,6 +219,17 @@ impl Surface {
                 self.wl_surface().commit();
                 return Ok(());
             }
+        } else if self.renderer.is_animated() {
+            // Keep receiving frame callbacks to play the animation
+            self.request_frame(qh, time, self.renderer.next_animation_time());
+            if self.window_drawn && !self.renderer.needs_redraw() {
+                // The current frame is still being shown
+                self.wl_surface().commit();
+                return Ok(());
+            }
+        } else if self.window_drawn && !self.renderer.needs_redraw() {
+            // Nothing has changed since the last frame, skip drawing and swapping the buffers
+            return Ok(());
         }
 
         unsafe { self.renderer.draw()? }
@@ -177,8 +251,53 @@ impl Surface {
         Ok(())
     }
 
+    /// Request a frame callback, right away or once the frame at `next_time` is due, so that
+    /// the daemon does not wake up at every vertical sync when the frames are limited
+    fn request_frame(
+        &mut self,
+        qh: &QueueHandle<Wpaperd>,
+        time: Option<u32>,
+        next_time: Option<u32>,
+    ) {
+        let delay = match (time, next_time) {
+            (Some(time), Some(next_time)) if next_time > time => next_time - time,
+            _ => {
+                self.wl_surface.frame(qh, self.wl_surface.clone());
+                return;
+            }
+        };
+        if self.frame_timer.is_some() {
+            return;
+        }
+        let name = self.name();
+        let timer_qh = qh.clone();
+        let timer = Timer::from_duration(Duration::from_millis(delay.into()));
+        match self.event_loop_handle.insert_source(
+            timer,
+            move |_deadline, _: &mut (), wpaperd: &mut Wpaperd| {
+                if let Some(surface) = wpaperd.surface_from_name(&name) {
+                    surface.frame_timer = None;
+                    surface
+                        .wl_surface
+                        .frame(&timer_qh, surface.wl_surface.clone());
+                    surface.wl_surface.commit();
+                }
+                TimeoutAction::Drop
+            },
+        ) {
+            Ok(registration_token) => self.frame_timer = Some(registration_token),
+            Err(err) => {
+                error!("unable to insert the frame timer: {err}");
+                self.wl_surface.frame(qh, self.wl_surface.clone());
+            }
+        }
+    }
+
     // Call surface::frame when this return false
     pub fn load_wallpaper(&mut self, qh: &QueueHandle<Wpaperd>) -> Result<bool> {
+        if let Some(color_source) = self.wallpaper_info.color_source {
+            return self.load_color_source(color_source);
+        }
         Ok(loop {
             // If we were not already trying to load an image
             if self.loading_image.is_none() {
@@ -209,37 +328,39 @@ impl Surface {
                 break true;
             }
 
-            let res = self
-                .image_loader
-                .borrow_mut()
-                .background_load(image_path.to_owned(), self.name());
+            let identity = ImageIdentity::new(&image_path);
+            // A wallpaper shown recently does not need to be decoded again
+            if let Some(identity) = identity
+                .as_ref()
+                .filter(|identity| self.renderer.is_cached(identity))
+            {
+                self.egl_context.make_current()?;
+                self.renderer.load_cached_wallpaper(
+                    identity,
+                    self.wallpaper_info.mode,
+                    self.wallpaper_info.offset,
+                )?;
+                self.wallpaper_loaded(image_path, index, true);
+                break true;
+            }
+
+            let res = self.image_loader.borrow_mut().background_load(
+                image_path.to_owned(),
+                self.name(),
+                self.renderer.max_texture_size(),
+            );
             match res {
                 crate::image_loader::ImageLoaderStatus::Loaded(data) => {
                     // Renderer::load_wallpaper load the wallpaper in a openGL texture
                     // Set the correct opengl context
                     self.egl_context.make_current()?;
-                    self.renderer.load_wallpaper(
-                        data.into(),
+                    let loaded = self.renderer.load_wallpaper(
+                        data,
+                        identity,
                         self.wallpaper_info.mode,
                         self.wallpaper_info.offset,
                     )?;
-
-                    let transition_time = if self.skip_next_transition {
-                        0
-                    } else {
-                        self.wallpaper_info.transition_time
-                    };
-                    self.skip_next_transition = false;
-
-                    if self.image_picker.is_reloading() {
-                        self.image_picker.reloaded();
-                    } else {
-                        self.image_picker.update_current_image(image_path, index);
-                        self.renderer.start_transition(transition_time);
-                    }
-                    // Restart the counter
-                    self.loading_image_tries = 0;
-                    self.loading_image = None;
+                    self.wallpaper_loaded(image_path, index, loaded);
                     break true;
                 }
                 crate::image_loader::ImageLoaderStatus::Waiting => {
@@ -260,6 +381,65 @@ impl Surface {
         })
     }
 
+    /// Start the transition to the wallpaper that has just been loaded by the renderer
+    fn wallpaper_loaded(&mut self, image_path: PathBuf, index: usize, loaded: bool) {
+        self.loaded_color_source = None;
+
+        let transition_time = if self.skip_next_transition {
+            0
+        } else {
+            self.wallpaper_info.transition_time
+        };
+        self.skip_next_transition = false;
+
+        if self.image_picker.is_reloading() {
+            self.image_picker.reloaded();
+        } else {
+            self.image_picker.update_current_image(image_path, index);
+            // Do not transition from the image to itself
+            if loaded {
+                self.renderer.start_transition(transition_time);
+            }
+        }
+        // Restart the counter
+        self.loading_image_tries = 0;
+        self.loading_image = None;
+    }
+
+    fn load_color_source(&mut self, color_source: ColorSource) -> Result<bool> {
+        if self.loaded_color_source == Some(color_source) || self.renderer.transition_running() {
+            return Ok(true);
+        }
+        self.egl_context.make_current()?;
+        let (mode, offset) = self.background_mode();
+        self.renderer.load_wallpaper(
+            ImageData::new(ImageFrames::Static(color_source.image())),
+            None,
+            mode,
+            offset,
+        )?;
+        let transition_time = if self.skip_next_transition {
+            0
+        } else {
+            self.wallpaper_info.transition_time
+        };
+        self.skip_next_transition = false;
+        self.renderer.start_transition(transition_time);
+        self.loaded_color_source = Some(color_source);
+
+        Ok(true)
+    }
+
+    /// The mode and offset used to draw the wallpaper. A color always covers the display,
+    /// the small image created by [ColorSource::image] gets extended to its borders
+    fn background_mode(&self) -> (BackgroundMode, Option<[f32; 2]>) {
+        if self.wallpaper_info.color_source.is_some() {
+            (BackgroundMode::FitBorderColor, None)
+        } else {
+            (self.wallpaper_info.mode, self.wallpaper_info.offset)
+        }
+    }
+
     pub fn name(&self) -> String {
         self.info.borrow().name.to_string()
     }
@@ -281,6 +461,13 @@ impl Surface {
         self.egl_context.make_current().with_context(|| {
             format!("unable to switch the openGL context for display {display_name}")
         })?;
+        // The new EGL surface has the default swap interval
+        if let Err(err) = self
+            .egl_context
+            .set_swap_interval(self.wallpaper_info.swap_interval)
+        {
+            error!("{err:?}");
+        }
         self.renderer.resize().with_context(|| {
             format!("unable to resize the GL window for display {display_name}")
         })?;
@@ -309,16 +496,13 @@ impl Surface {
         if info.change_transform(transform) {
             drop(info);
             self.wl_surface.set_buffer_transform(transform);
-            if let Err(err) = self
-                .resize(qh)
-                .and_then(|_| {
-                    self.renderer
-                        .set_mode(self.wallpaper_info.mode, self.wallpaper_info.offset)
-                })
-                .and_then(|_| unsafe { self.renderer.set_projection_matrix(transform) })
-            {
+            if let Err(err) = self.resize(qh).and_then(|_| {
+                let (mode, offset) = self.background_mode();
+                self.renderer.set_mode(mode, offset)
+            }) {
                 error!("{err:?}");
             }
+            self.renderer.set_projection_matrix(transform);
         }
     }
 
@@ -369,6 +553,13 @@ impl Surface {
             path_changed,
             wallpaper_info.drawn_images_queue_size,
         );
+        if self.wallpaper_info.color_source != wallpaper_info.color_source {
+            if self.wallpaper_info.color_source.is_none() {
+                // Draw the image again, even if it has not changed in the meantime
+                self.image_picker.reload();
+            }
+            self.queue_draw(qh);
+        }
         if path_changed {
             // ask the image_picker to pick a new a image
             self.image_picker.next_image(&self.wallpaper_info.path, qh);
@@ -413,9 +604,130 @@ impl Surface {
         if self.wallpaper_info.mode != wallpaper_info.mode
             || self.wallpaper_info.offset != wallpaper_info.offset
         {
+            let (mode, offset) = self.background_mode();
+            match self.egl_context.make_current().and_then(|_| {
+                if path_changed {
+                    // The new wallpaper will be shown with the new mode
+                    self.renderer.set_mode(mode, offset).map(|_| false)
+                } else {
+                    self.renderer.animate_mode_change(mode, offset)
+                }
+            }) {
+                Ok(true) => self
+                    .renderer
+                    .start_transition(self.wallpaper_info.transition_time),
+                Ok(false) => {}
+                Err(err) => error!("{err:?}"),
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if (
+            self.wallpaper_info.flip_horizontal,
+            self.wallpaper_info.flip_vertical,
+        ) != (wallpaper_info.flip_horizontal, wallpaper_info.flip_vertical)
+        {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer.update_flip(
+                    self.wallpaper_info.flip_horizontal,
+                    self.wallpaper_info.flip_vertical,
+                )
+            }) {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.tile_scale != wallpaper_info.tile_scale {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer
+                    .update_tile_scale(self.wallpaper_info.tile_scale)
+            }) {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if (
+            self.wallpaper_info.ken_burns_zoom,
+            self.wallpaper_info.ken_burns_time,
+        ) != (wallpaper_info.ken_burns_zoom, wallpaper_info.ken_burns_time)
+        {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer.update_ken_burns(
+                    self.wallpaper_info.ken_burns_zoom,
+                    self.wallpaper_info.ken_burns_time,
+                )
+            }) {
+                error!("{err:?}");
+            }
+            // Start or stop receiving the frame callbacks
+            self.queue_draw(qh);
+        }
+        if (
+            self.wallpaper_info.padding,
+            self.wallpaper_info.corner_radius,
+        ) != (wallpaper_info.padding, wallpaper_info.corner_radius)
+        {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer.update_padding(
+                    self.wallpaper_info.padding,
+                    self.wallpaper_info.corner_radius,
+                )
+            }) {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.fill_color != wallpaper_info.fill_color {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer
+                    .update_fill_color(self.wallpaper_info.fill_color)
+            }) {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.anisotropy != wallpaper_info.anisotropy {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer
+                    .update_anisotropy(self.wallpaper_info.anisotropy)
+            }) {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.wrap_mode != wallpaper_info.wrap_mode {
             if let Err(err) = self.egl_context.make_current().and_then(|_| {
                 self.renderer
-                    .set_mode(self.wallpaper_info.mode, self.wallpaper_info.offset)
+                    .update_wrap_mode(self.wallpaper_info.wrap_mode)
             }) {
                 error!("{err:?}");
             }
@@ -426,6 +738,142 @@ impl Surface {
                 }
             }
         }
+        if self.wallpaper_info.texture_filter != wallpaper_info.texture_filter {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer
+                    .update_texture_filter(self.wallpaper_info.texture_filter)
+            }) {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if (
+            self.wallpaper_info.brightness,
+            self.wallpaper_info.contrast,
+            self.wallpaper_info.gamma,
+        ) != (
+            wallpaper_info.brightness,
+            wallpaper_info.contrast,
+            wallpaper_info.gamma,
+        ) {
+            self.renderer.set_brightness(self.wallpaper_info.brightness);
+            self.renderer.set_contrast(self.wallpaper_info.contrast);
+            self.renderer.set_gamma(self.wallpaper_info.gamma);
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.opacity != wallpaper_info.opacity {
+            self.renderer.set_opacity(self.wallpaper_info.opacity);
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.tint != wallpaper_info.tint {
+            self.renderer.set_tint(self.wallpaper_info.tint);
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if (
+            self.wallpaper_info.vignette_strength,
+            self.wallpaper_info.vignette_radius,
+        ) != (
+            wallpaper_info.vignette_strength,
+            wallpaper_info.vignette_radius,
+        ) {
+            self.renderer.set_vignette(
+                self.wallpaper_info.vignette_strength,
+                self.wallpaper_info.vignette_radius,
+            );
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
+        if self.wallpaper_info.srgb != wallpaper_info.srgb {
+            let info = self.info.borrow();
+            let (width, height) = (info.adjusted_width(), info.adjusted_height());
+            drop(info);
+            if let Err(err) = self
+                .egl_context
+                .update_srgb(&self.wl_surface, self.wallpaper_info.srgb, width, height)
+                .and_then(|_| self.egl_context.make_current())
+                .and_then(|_| {
+                    self.egl_context
+                        .set_swap_interval(self.wallpaper_info.swap_interval)
+                })
+                .and_then(|_| self.renderer.update_srgb(self.egl_context.srgb()))
+            {
+                error!("{err:?}");
+            }
+            // Load the texture again with the new format
+            self.image_picker.reload();
+            self.queue_draw(qh);
+        }
+        if self.wallpaper_info.bit_depth != wallpaper_info.bit_depth {
+            // The EGL configuration cannot be changed after the context has been created
+            warn!(
+                "bit-depth will be applied when the display {} is connected again",
+                self.name()
+            );
+        }
+        if self.wallpaper_info.mipmaps != wallpaper_info.mipmaps {
+            if let Err(err) = self
+                .egl_context
+                .make_current()
+                .and_then(|_| self.renderer.update_mipmaps(self.wallpaper_info.mipmaps))
+            {
+                error!("{err:?}");
+            }
+            // Load the texture again to generate its mipmap
+            self.loaded_color_source = None;
+            self.image_picker.reload();
+            self.queue_draw(qh);
+        }
+        if self.wallpaper_info.premultiply_alpha != wallpaper_info.premultiply_alpha {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.renderer
+                    .update_premultiply_alpha(self.wallpaper_info.premultiply_alpha)
+            }) {
+                error!("{err:?}");
+            }
+            // Load the texture again with the new colors
+            self.loaded_color_source = None;
+            self.image_picker.reload();
+            self.queue_draw(qh);
+        }
+        if self.wallpaper_info.blur != wallpaper_info.blur {
+            if let Err(err) = self
+                .egl_context
+                .make_current()
+                .and_then(|_| self.renderer.set_blur(self.wallpaper_info.blur))
+            {
+                error!("{err:?}");
+            }
+            if !path_changed {
+                // We should draw immediately
+                if let Err(err) = self.draw(qh, None) {
+                    warn!("{err:?}");
+                }
+            }
+        }
         if self.wallpaper_info.transition != wallpaper_info.transition {
             match self.egl_context.make_current() {
                 Ok(_) => {
@@ -438,6 +886,31 @@ impl Surface {
                 }
             }
         }
+        if self.wallpaper_info.swap_interval != wallpaper_info.swap_interval {
+            if let Err(err) = self.egl_context.make_current().and_then(|_| {
+                self.egl_context
+                    .set_swap_interval(self.wallpaper_info.swap_interval)
+            }) {
+                error!("{err:?}");
+            }
+        }
+        if self.wallpaper_info.max_fps != wallpaper_info.max_fps {
+            self.renderer.update_max_fps(self.wallpaper_info.max_fps);
+        }
+        if self.wallpaper_info.easing != wallpaper_info.easing {
+            self.renderer.set_easing(self.wallpaper_info.easing);
+        }
+        if self.wallpaper_info.fade_from_black != wallpaper_info.fade_from_black {
+            self.renderer
+                .set_fade_from_black(self.wallpaper_info.fade_from_black);
+        }
+        if self.wallpaper_info.texture_cache_size != wallpaper_info.texture_cache_size {
+            if let Err(err) = self.egl_context.make_current() {
+                error!("{err:?}");
+            }
+            self.renderer
+                .update_texture_cache_size(self.wallpaper_info.texture_cache_size);
+        }
         if self.wallpaper_info.drawn_images_queue_size != wallpaper_info.drawn_images_queue_size {
             self.image_picker
                 .update_queue_size(self.wallpaper_info.drawn_images_queue_size);
@@ -547,6 +1020,18 @@ impl Surface {
         remaining_duration(duration, self.image_picker.image_changed_instant)
     }
 
+    /// Draw the wallpaper again and save it as an image in `path`, without showing the frame
+    pub fn capture(&mut self, path: &Path) -> Result<()> {
+        self.egl_context.make_current()?;
+        // Drawing does not change what is shown until the buffers are swapped, and the
+        // next frame is drawn from scratch anyway
+        unsafe { self.renderer.draw()? };
+        self.renderer
+            .capture()?
+            .save(path)
+            .with_context(|| format!("unable to save the capture to {path:?}"))
+    }
+
     /// Indicate to the main event loop that the automatic wallpaper sequence for this [`Surface`]
     /// should be paused.
     /// The actual pausing/resuming is handled in [`Surface::handle_pause_state`]
@@ -594,8 +1079,13 @@ impl Surface {
     }
 }
 
-fn black_image() -> RgbaImage {
-    RgbaImage::from_raw(1, 1, vec![0, 0, 0, 255]).unwrap()
+impl Drop for Surface {
+    fn drop(&mut self) {
+        // The objects of the renderer are deleted in the context that is current
+        if let Err(err) = self.egl_context.make_current() {
+            error!("{err:?}");
+        }
+    }
 }
 
 fn remaining_duration(duration: Duration, image_changed: Instant) -> Option<Duration> {
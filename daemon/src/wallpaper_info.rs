@@ -1,5 +1,6 @@
 use std::{path::PathBuf, time::Duration};
 
+use image::{Rgba, RgbaImage};
 use serde::Deserialize;
 
 use crate::{image_picker::ImagePicker, render::Transition};
@@ -12,17 +13,98 @@ pub struct WallpaperInfo {
     pub sorting: Option<Sorting>,
     pub mode: BackgroundMode,
     pub drawn_images_queue_size: usize,
+    /// Number of textures of the previous wallpapers kept in the GPU memory, so that they
+    /// can be shown again without loading the image. 0 disables the cache
+    pub texture_cache_size: usize,
     pub transition_time: u32,
 
     /// Determines if we should show the transition between black and first
     /// wallpaper. `false` means we instantly cut to the first wallpaper,
     /// `true` means we fade from black to the first wallpaper.
     pub initial_transition: bool,
+    /// Fade every wallpaper in from black, like the initial transition
+    pub fade_from_black: bool,
     pub transition: Transition,
+    /// Easing curve applied to the progress of the transition
+    pub easing: Easing,
+    /// Maximum number of frames per second drawn during the transition
+    pub max_fps: Option<u32>,
+    /// Number of vertical syncs to wait for when swapping the buffers, 0 does not wait
+    pub swap_interval: i32,
 
-    /// Determine the offset for the wallpaper to be drawn into the screen
+    /// Determine the horizontal and vertical offset for the wallpaper to be drawn into
+    /// the screen, i.e. which part of the image is kept when it gets cropped
     /// Must be from 0.0 to 1.0, by default is 0.0 in tile mode and 0.5 in all the others
-    pub offset: Option<f32>,
+    pub offset: Option<[f32; 2]>,
+
+    /// Mirror the wallpaper horizontally and vertically
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+
+    /// Size of the tiles relative to the size of the image, in tile mode
+    pub tile_scale: f32,
+
+    /// Zoom reached by the Ken Burns effect in center mode, 1.0 disables it
+    pub ken_burns_zoom: f32,
+    /// Milliseconds needed by the Ken Burns effect to zoom in and out again
+    pub ken_burns_time: u32,
+
+    /// Space left around the wallpaper, filled with the fill color, in logical pixels
+    pub padding: u32,
+    /// Radius of the corners of the wallpaper, in logical pixels
+    pub corner_radius: u32,
+
+    /// Color used to fill the empty space around the wallpaper in fit mode
+    pub fill_color: [f32; 4],
+
+    /// Filter used to sample the wallpaper when it gets scaled
+    pub texture_filter: TextureFilter,
+    /// Wrap of the texture in stretch, center and tile modes, None for the default of each
+    /// mode. The other modes need their own wrap to show the fill color
+    pub wrap_mode: Option<WrapMode>,
+    /// Level of anisotropic filtering, 1.0 disables it
+    pub anisotropy: f32,
+    /// Generate the mipmap of the wallpaper, to scale it down smoothly at the cost of a
+    /// third more video memory
+    pub mipmaps: bool,
+
+    /// Color adjustments applied to the wallpaper, 1.0 leaves it untouched
+    pub brightness: f32,
+    pub contrast: f32,
+    pub gamma: f32,
+    /// Multiplier of the red, green and blue channels, applied after the color adjustments
+    pub tint: [f32; 3],
+
+    /// How much the edges of the wallpaper are darkened, 0.0 disables the vignette
+    pub vignette_strength: f32,
+    /// Distance from the center where the vignette starts, from 0.0 to the corners at 1.0
+    pub vignette_radius: f32,
+
+    /// Opacity of the wallpaper, what is below it shows through when lower than 1.0
+    pub opacity: f32,
+
+    /// Standard deviation of the gaussian blur applied to the wallpaper, in pixels
+    pub blur: f32,
+
+    /// Blend the wallpapers in linear colors, converting them from and to sRGB
+    pub srgb: bool,
+    /// Bits per color channel requested for the framebuffer, 10 reduces the banding of the
+    /// colors computed by the shader on displays that support it. Only the opaque 16-bit images
+    /// get 10-bit textures
+    pub bit_depth: u8,
+    /// Multiply the colors by their alpha when loading the wallpaper, so that the
+    /// transparent parts blend correctly with the fill color and the compositor
+    pub premultiply_alpha: bool,
+
+    /// Draw a color instead of the images in `path`
+    pub color_source: Option<ColorSource>,
+}
+
+impl WallpaperInfo {
+    /// Transparent black, i.e. what the compositor shows under the wallpaper
+    pub const DEFAULT_FILL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+    /// A full minute to zoom in and out, slow enough to not be distracting
+    pub const DEFAULT_KEN_BURNS_TIME: u32 = 60000;
 }
 
 impl Default for WallpaperInfo {
@@ -34,10 +116,39 @@ impl Default for WallpaperInfo {
             sorting: None,
             mode: BackgroundMode::default(),
             drawn_images_queue_size: ImagePicker::DEFAULT_DRAWN_IMAGES_QUEUE_SIZE,
+            texture_cache_size: 0,
             transition_time: Transition::Fade {}.default_transition_time(),
             initial_transition: true,
+            fade_from_black: false,
             transition: Transition::Fade {},
+            easing: Easing::default(),
+            max_fps: None,
+            swap_interval: 1,
             offset: None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            tile_scale: 1.0,
+            ken_burns_zoom: 1.0,
+            ken_burns_time: WallpaperInfo::DEFAULT_KEN_BURNS_TIME,
+            padding: 0,
+            corner_radius: 0,
+            fill_color: WallpaperInfo::DEFAULT_FILL_COLOR,
+            texture_filter: TextureFilter::default(),
+            wrap_mode: None,
+            anisotropy: 1.0,
+            mipmaps: true,
+            brightness: 1.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            tint: [1.0, 1.0, 1.0],
+            vignette_strength: 0.0,
+            vignette_radius: 0.5,
+            opacity: 1.0,
+            blur: 0.0,
+            srgb: false,
+            bit_depth: 8,
+            premultiply_alpha: false,
+            color_source: None,
         }
     }
 }
@@ -63,4 +174,118 @@ pub enum BackgroundMode {
     Fit,
     Tile,
     FitBorderColor,
+    Original,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    /// Keep the pixels sharp, useful for pixel-art wallpapers
+    Nearest,
+}
+
+/// How the wallpaper is sampled outside of its image, i.e. around the tiles
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WrapMode {
+    ClampToEdge,
+    Repeat,
+    MirroredRepeat,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map the linear progress `t`, from 0.0 to 1.0, on the easing curve
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// The offset of the wallpaper, either the same for both axes or one per axis
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Offset {
+    Both(f32),
+    Axes([f32; 2]),
+}
+
+impl Offset {
+    #[inline]
+    pub fn axes(self) -> [f32; 2] {
+        match self {
+            Offset::Both(offset) => [offset, offset],
+            Offset::Axes(axes) => axes,
+        }
+    }
+}
+
+/// A wallpaper drawn without an image file
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorSource {
+    Color([f32; 4]),
+    Gradient(Gradient),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Gradient {
+    pub from: [f32; 4],
+    pub to: [f32; 4],
+    /// The gradient goes from top to bottom by default, or from left to right
+    #[serde(default)]
+    pub horizontal: bool,
+}
+
+impl ColorSource {
+    /// Number of steps in the gradient, the rest is interpolated by openGL. Each step is
+    /// stored with 8 bits per channel, even with a 10-bit framebuffer
+    const GRADIENT_STEPS: u32 = 256;
+
+    /// Create a small image that covers the display when drawn in `fit-border-color` mode
+    pub fn image(&self) -> RgbaImage {
+        match self {
+            ColorSource::Color(color) => RgbaImage::from_pixel(1, 1, to_rgba(*color)),
+            ColorSource::Gradient(gradient) => {
+                let (width, height) = if gradient.horizontal {
+                    (Self::GRADIENT_STEPS, 1)
+                } else {
+                    (1, Self::GRADIENT_STEPS)
+                };
+                RgbaImage::from_fn(width, height, |x, y| {
+                    let t = (x + y) as f32 / (Self::GRADIENT_STEPS - 1) as f32;
+                    let mut color = [0.0; 4];
+                    for (i, channel) in color.iter_mut().enumerate() {
+                        *channel = gradient.from[i] + (gradient.to[i] - gradient.from[i]) * t;
+                    }
+                    to_rgba(color)
+                })
+            }
+        }
+    }
+}
+
+fn to_rgba(color: [f32; 4]) -> Rgba<u8> {
+    Rgba(color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
 }
@@ -24,6 +24,7 @@ use crate::config::Config;
 use crate::display_info::DisplayInfo;
 use crate::filelist_cache::FilelistCache;
 use crate::image_loader::ImageLoader;
+use crate::render::ShareContexts;
 use crate::surface::Surface;
 use crate::wallpaper_groups::WallpaperGroups;
 use crate::wallpaper_info::WallpaperInfo;
@@ -37,9 +38,13 @@ pub struct Wpaperd {
     pub surfaces: Vec<Surface>,
     pub config: Config,
     egl_display: egl::Display,
+    /// Puts the contexts of all the displays in the same share group
+    pub egl_share_contexts: RefCell<ShareContexts>,
     pub filelist_cache: Rc<RefCell<FilelistCache>>,
     pub image_loader: Rc<RefCell<ImageLoader>>,
     pub wallpaper_groups: Rc<RefCell<WallpaperGroups>>,
+    /// Used by the surfaces to wait for their next frame without a frame callback
+    pub event_loop_handle: LoopHandle<'static, Wpaperd>,
 }
 
 impl Wpaperd {
@@ -50,6 +55,7 @@ impl Wpaperd {
         egl_display: egl::Display,
         filelist_cache: Rc<RefCell<FilelistCache>>,
         wallpaper_groups: Rc<RefCell<WallpaperGroups>>,
+        event_loop_handle: LoopHandle<'static, Wpaperd>,
     ) -> Result<Self> {
         let shm_state = Shm::bind(globals, qh)?;
 
@@ -64,9 +70,11 @@ impl Wpaperd {
             surfaces: Vec::new(),
             config,
             egl_display,
+            egl_share_contexts: RefCell::new(ShareContexts::new(egl_display)),
             filelist_cache,
             image_loader,
             wallpaper_groups,
+            event_loop_handle,
         })
     }
 
@@ -232,7 +240,7 @@ impl OutputHandler for Wpaperd {
             }
         };
 
-        self.surfaces.push(Surface::new(
+        match Surface::new(
             self,
             layer,
             output,
@@ -240,7 +248,13 @@ impl OutputHandler for Wpaperd {
             wallpaper_info,
             self.egl_display,
             qh,
-        ));
+        ) {
+            Ok(surface) => self.surfaces.push(surface),
+            Err(err) => error!(
+                "unable to create the surface for display {}: {err:?}",
+                name.bold().magenta()
+            ),
+        }
     }
 
     fn update_output(
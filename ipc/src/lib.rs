@@ -13,6 +13,7 @@ pub enum IpcMessage {
     TogglePauseWallpaper { monitors: Vec<String> },
     AllWallpapers,
     ReloadWallpaper { monitors: Vec<String> },
+    CaptureWallpaper { monitor: String, path: PathBuf },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,6 +27,7 @@ pub enum IpcResponse {
 pub enum IpcError {
     MonitorNotFound { monitor: String },
     DrawErrors(Vec<(String, String)>),
+    CaptureError { monitor: String, error: String },
 }
 
 pub fn socket_path() -> Result<PathBuf, BaseDirectoriesError> {